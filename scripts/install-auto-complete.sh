@@ -10,8 +10,9 @@ RESET="\033[0m"
 
 # Detect the directory where this script is located
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
-COMPLETIONS_DIR="$(dirname "$SCRIPT_DIR")/completions"
 
+# Completions are generated by kee itself: prefer the installed binary, then a local build
+KEE_BIN="${KEE_BIN:-$(command -v kee || echo "$(dirname "$SCRIPT_DIR")/target/release/kee")}"
 
 # Function to detect current shell
 detect_shell() {
@@ -56,7 +57,7 @@ install_bash_completion() {
     mkdir -p "$HOME/.kee"
 
     # Copy completion file
-    if cp "$COMPLETIONS_DIR/kee.bash" "$completion_file" 2>/dev/null; then
+    if "$KEE_BIN" completions bash > "$completion_file" 2>/dev/null; then
         echo -e " [✓] Bash auto-completion installed to ${BOLD_WHITE}~/.kee/.kee_completion.bash${RESET}"
 
         # Add source line to shell config
@@ -96,7 +97,7 @@ install_zsh_completion() {
     mkdir -p "$completion_dir"
 
     # Copy completion file
-    if cp "$COMPLETIONS_DIR/_kee" "$completion_file" 2>/dev/null; then
+    if "$KEE_BIN" completions zsh > "$completion_file" 2>/dev/null; then
         # Add fpath configuration to .zshrc
         local fpath_line="fpath=(~/.kee/completions \$fpath)"
         local compinit_line="autoload -Uz compinit && compinit"
@@ -130,7 +131,7 @@ install_fish_completion() {
     mkdir -p "$completion_dir"
 
     # Copy completion file
-    if cp "$COMPLETIONS_DIR/kee.fish" "$completion_file" 2>/dev/null; then
+    if "$KEE_BIN" completions fish > "$completion_file" 2>/dev/null; then
         return 0
     else
         echo -e " [X] Failed to install fish auto-completion."
@@ -138,9 +139,10 @@ install_fish_completion() {
     fi
 }
 
-# Check if completion files exist
-if [[ ! -d "$COMPLETIONS_DIR" ]]; then
-    echo -e " [X] Auto-completion files not found at $COMPLETIONS_DIR"
+# Check that kee can generate the completions
+if [[ ! -x "$KEE_BIN" ]]; then
+    echo -e " [X] The kee binary was not found at $KEE_BIN"
+    echo -e "     Build it first with ${BOLD_WHITE}cargo build --release${RESET}, or set ${BOLD_WHITE}KEE_BIN${RESET}"
 else
     # Detect shell
     CURRENT_SHELL=$(detect_shell)
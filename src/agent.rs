@@ -0,0 +1,277 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::credentials::Credentials;
+
+/// The variables the AWS SDKs and CLI read to use a container credentials endpoint
+pub const FULL_URI_VAR: &str = "AWS_CONTAINER_CREDENTIALS_FULL_URI";
+pub const AUTHORIZATION_TOKEN_VAR: &str = "AWS_CONTAINER_AUTHORIZATION_TOKEN";
+
+/// Credentials are refreshed this long before they expire, so clients never
+/// get credentials that are about to run out
+pub const REFRESH_MINUTES: i64 = 15;
+
+/// Longest request head the agent reads; clients only send a few headers
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A random token clients must send in the `Authorization` header
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Whether cached credentials are missing or expire within the refresh window.
+/// Credentials without an expiration never need a refresh.
+pub fn needs_refresh(credentials: Option<&Credentials>, now: DateTime<Utc>) -> bool {
+    match credentials {
+        None => true,
+        Some(credentials) => credentials.expiration.is_some_and(|expiration| {
+            expiration - now <= chrono::Duration::minutes(REFRESH_MINUTES)
+        }),
+    }
+}
+
+fn is_expired(credentials: &Credentials, now: DateTime<Utc>) -> bool {
+    credentials
+        .expiration
+        .is_some_and(|expiration| expiration <= now)
+}
+
+/// The body of a container credentials response
+pub fn credentials_json(credentials: &Credentials) -> String {
+    let mut body = json!({
+        "AccessKeyId": credentials.access_key_id,
+        "SecretAccessKey": credentials.secret_access_key,
+    });
+    if let Some(token) = &credentials.session_token {
+        body["Token"] = json!(token);
+    }
+    if let Some(expiration) = credentials.expiration {
+        body["Expiration"] = json!(expiration.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    body.to_string()
+}
+
+/// Compare without stopping at the first difference, so timing reveals nothing
+/// about the token
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+}
+
+/// Read the request line and headers of an HTTP/1.x request; the body is ignored
+pub fn read_request(reader: impl Read) -> io::Result<Request> {
+    let mut reader = BufReader::new(reader.take(MAX_REQUEST_BYTES));
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("Malformed request line"));
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        authorization: None,
+    };
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Incomplete request"));
+        }
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            return Ok(request);
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                request.authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Self { status: 200, body }
+    }
+
+    /// An error in the JSON shape the SDKs log
+    fn error(status: u16, code: &str, message: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "code": code, "message": message }).to_string(),
+        }
+    }
+
+    /// The message of an error response
+    pub fn error_message(&self) -> Option<String> {
+        if self.status == 200 {
+            return None;
+        }
+        let body: serde_json::Value = serde_json::from_str(&self.body).ok()?;
+        body["message"].as_str().map(str::to_string)
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Service Unavailable",
+        }
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            self.body
+        )?;
+        writer.flush()
+    }
+}
+
+/// A localhost server implementing the container credentials protocol
+/// (`AWS_CONTAINER_CREDENTIALS_FULL_URI`), handing out the credentials `fetch`
+/// returns and fetching new ones before they expire
+pub struct Agent<F> {
+    listener: TcpListener,
+    token: String,
+    cache: Mutex<Option<Credentials>>,
+    fetch: F,
+}
+
+impl<F: Fn() -> Result<Credentials, String>> Agent<F> {
+    /// Listen on the loopback interface; port 0 picks a free port
+    pub fn bind(port: u16, token: String, fetch: F) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(("127.0.0.1", port))?,
+            token,
+            cache: Mutex::new(None),
+            fetch,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn url(&self) -> io::Result<String> {
+        Ok(format!("http://{}/", self.local_addr()?))
+    }
+
+    /// The environment pointing a client at the agent
+    pub fn env(&self) -> io::Result<Vec<(&'static str, String)>> {
+        Ok(vec![
+            (FULL_URI_VAR, self.url()?),
+            (AUTHORIZATION_TOKEN_VAR, self.token.clone()),
+        ])
+    }
+
+    /// Start with credentials fetched beforehand
+    pub fn prime(&self, credentials: Credentials) {
+        *self.cache.lock().unwrap() = Some(credentials);
+    }
+
+    /// The cached credentials, refreshed first when they are about to expire.
+    /// If the refresh fails, credentials that are still valid are served.
+    fn credentials(&self) -> Result<Credentials, String> {
+        let mut cache = self.cache.lock().unwrap();
+        let now = Utc::now();
+        if needs_refresh(cache.as_ref(), now) {
+            match (self.fetch)() {
+                Ok(credentials) => *cache = Some(credentials),
+                Err(e) => match cache.as_ref() {
+                    Some(credentials) if !is_expired(credentials, now) => {}
+                    _ => return Err(e),
+                },
+            }
+        }
+        Ok(cache.clone().expect("credentials were just fetched"))
+    }
+
+    pub fn respond(&self, request: &Request) -> Response {
+        let authorized = request
+            .authorization
+            .as_deref()
+            .is_some_and(|given| tokens_match(&self.token, given));
+        if !authorized {
+            return Response::error(401, "Unauthorized", "Missing or wrong authorization token");
+        }
+        if request.path != "/" {
+            return Response::error(404, "NotFound", "Unknown path");
+        }
+        if request.method != "GET" {
+            return Response::error(405, "MethodNotAllowed", "Only GET is supported");
+        }
+
+        match self.credentials() {
+            Ok(credentials) => Response::ok(credentials_json(&credentials)),
+            Err(e) => Response::error(503, "CredentialsUnavailable", &e),
+        }
+    }
+
+    fn handle(&self, stream: &mut TcpStream) -> io::Result<(Request, Response)> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let (request, response) = match read_request(&mut *stream) {
+            Ok(request) => {
+                let response = self.respond(&request);
+                (request, response)
+            }
+            Err(e) => (
+                Request::default(),
+                Response::error(400, "BadRequest", &e.to_string()),
+            ),
+        };
+        response.write_to(&mut *stream)?;
+        Ok((request, response))
+    }
+
+    /// Answer requests one at a time until the process ends, calling `log`
+    /// for each one
+    pub fn serve(&self, mut log: impl FnMut(&Request, &Response)) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            if let Ok((request, response)) = self.handle(&mut stream) {
+                log(&request, &response);
+            }
+        }
+        Ok(())
+    }
+}
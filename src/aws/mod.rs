@@ -2,12 +2,14 @@
 
 use configparser::ini::Ini;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+use crate::chain::ChainedRole;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct ProfileInfo {
     pub profile_name: String,
     pub sso_start_url: String,
@@ -15,10 +17,137 @@ pub struct ProfileInfo {
     pub sso_account_id: String,
     pub sso_role_name: String,
     pub session_name: String,
+    /// Default session length for `kee use`, e.g. `8h`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_session: Option<String>,
+    /// Free-form labels such as `client=acme` or `env=prod`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Command used by `kee console` to open links, e.g. `firefox -P acme {url}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<String>,
+    /// Default region for commands run with this profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Default AWS CLI output format (json, yaml, yaml-stream, text or table)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Extra environment variables, e.g. `TF_VAR_env=prod` or `KUBECONFIG`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Shell command run before the sub-shell starts; a failure aborts the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_enter: Option<String>,
+    /// Shell command run after the sub-shell exits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_exit: Option<String>,
+    /// Roles assumed in turn on top of the SSO role
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub role_chain: Vec<ChainedRole>,
+}
+
+impl ProfileInfo {
+    pub fn has_tag(&self, key: &str, value: &str) -> bool {
+        self.tags.get(key).map(String::as_str) == Some(value)
+    }
+
+    /// The keys kee maintains in this profile's `[profile]` section
+    pub fn aws_section_values(&self) -> Vec<(&str, &str)> {
+        let mut values = Vec::new();
+        if self.session_name.is_empty() {
+            values.push(("sso_start_url", self.sso_start_url.as_str()));
+            values.push(("sso_region", self.sso_region.as_str()));
+        } else {
+            values.push(("sso_session", self.session_name.as_str()));
+        }
+        values.push(("sso_account_id", self.sso_account_id.as_str()));
+        values.push(("sso_role_name", self.sso_role_name.as_str()));
+        values
+    }
+
+    /// Variables set for the sub-shell and commands run with this profile.
+    /// The custom `env` entries come last so they can override the defaults.
+    pub fn environment(&self) -> Vec<(&str, &str)> {
+        let mut vars = Vec::new();
+        if let Some(region) = &self.region {
+            vars.push(("AWS_REGION", region.as_str()));
+            vars.push(("AWS_DEFAULT_REGION", region.as_str()));
+        }
+        if let Some(output) = &self.output {
+            vars.push(("AWS_DEFAULT_OUTPUT", output.as_str()));
+        }
+        for (name, value) in &self.env {
+            vars.push((name.as_str(), value.as_str()));
+        }
+        vars
+    }
+
+    /// Take the SSO details from a freshly read profile, keeping kee's own settings
+    pub fn update_sso_details(&mut self, fresh: ProfileInfo) {
+        self.sso_start_url = fresh.sso_start_url;
+        self.sso_region = fresh.sso_region;
+        self.sso_account_id = fresh.sso_account_id;
+        self.sso_role_name = fresh.sso_role_name;
+        self.session_name = fresh.session_name;
+    }
+}
+
+/// The sso-sessions from `sessions` that neither a `[profile]` section nor
+/// `[default]` refers to any more
+pub fn unreferenced_sessions<'a>(
+    sessions: impl IntoIterator<Item = &'a str>,
+    sections: &HashMap<String, HashMap<String, Option<String>>>,
+) -> Vec<String> {
+    let referenced: Vec<&str> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("profile ") || name.as_str() == "default")
+        .filter_map(|(_, section)| section.get("sso_session")?.as_deref())
+        .collect();
+
+    let mut unreferenced: Vec<String> = sessions
+        .into_iter()
+        .filter(|session| !session.is_empty() && !referenced.contains(session))
+        .map(str::to_string)
+        .collect();
+    unreferenced.sort();
+    unreferenced.dedup();
+    unreferenced
+}
+
+/// Parse a `key=value` tag as given on the command line
+pub fn parse_tag(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("Invalid tag '{input}' (use key=value)")),
+    }
+}
+
+/// Parse a `NAME=value` environment variable as given on the command line
+pub fn parse_env_var(input: &str) -> Result<(String, String), String> {
+    let Some((name, value)) = input.split_once('=') else {
+        return Err(format!("Invalid variable '{input}' (use NAME=value)"));
+    };
+
+    if !is_env_name(name) {
+        return Err(format!("Invalid variable name '{name}'"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+pub fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 pub struct AwsManager {
     aws_config_file: PathBuf,
+    sso_cache_dir: PathBuf,
+    cli_cache_dir: PathBuf,
 }
 
 impl AwsManager {
@@ -31,8 +160,27 @@ impl AwsManager {
         })?;
 
         let aws_config_file = home_dir.join(".aws").join("config");
+        let sso_cache_dir = home_dir.join(".aws").join("sso").join("cache");
+        let cli_cache_dir = home_dir.join(".aws").join("cli").join("cache");
+
+        Ok(Self {
+            aws_config_file,
+            sso_cache_dir,
+            cli_cache_dir,
+        })
+    }
+
+    pub fn config_file(&self) -> &Path {
+        &self.aws_config_file
+    }
 
-        Ok(Self { aws_config_file })
+    pub fn sso_cache_dir(&self) -> &Path {
+        &self.sso_cache_dir
+    }
+
+    /// Where the AWS CLI caches role credentials
+    pub fn cli_cache_dir(&self) -> &Path {
+        &self.cli_cache_dir
     }
 
     pub fn load_config(&self) -> io::Result<Ini> {
@@ -67,6 +215,53 @@ impl AwsManager {
         self.save_config(&config)
     }
 
+    /// Create or update keys in a section of the AWS config file
+    pub fn set_section(&self, section_name: &str, values: &[(&str, &str)]) -> io::Result<()> {
+        let mut config = self.load_config()?;
+        for (key, value) in values {
+            config.set(section_name, key, Some(value.to_string()));
+        }
+        self.save_config(&config)
+    }
+
+    /// Write kee's view of a profile into its `[profile]` section
+    pub fn write_profile(&self, profile: &ProfileInfo) -> io::Result<()> {
+        let mut config = self.load_config()?;
+        let section_name = format!("profile {}", profile.profile_name);
+        for key in ["sso_session", "sso_start_url", "sso_region"] {
+            config.remove_key(&section_name, key);
+        }
+        for (key, value) in profile.aws_section_values() {
+            config.set(&section_name, key, Some(value.to_string()));
+        }
+        self.save_config(&config)
+    }
+
+    /// Move all keys of a section to a new name, returning false if it didn't exist
+    pub fn rename_section(&self, old_name: &str, new_name: &str) -> io::Result<bool> {
+        let mut config = self.load_config()?;
+        let Some(values) = config.remove_section(old_name) else {
+            return Ok(false);
+        };
+        for (key, value) in values {
+            config.set(new_name, &key, value);
+        }
+        self.save_config(&config)?;
+        Ok(true)
+    }
+
+    pub fn remove_sections(&self, section_names: &[String]) -> io::Result<()> {
+        let mut config = self.load_config()?;
+        for section_name in section_names {
+            config.remove_section(section_name);
+        }
+        self.save_config(&config)
+    }
+
+    pub fn has_section(&self, section_name: &str) -> io::Result<bool> {
+        Ok(self.load_config()?.get_map_ref().contains_key(section_name))
+    }
+
     pub fn remove_profile(&self, profile_name: &str) -> io::Result<()> {
         let mut config = self.load_config()?;
         let section_name = format!("profile {profile_name}");
@@ -130,6 +325,7 @@ impl AwsManager {
             sso_account_id,
             sso_role_name,
             session_name,
+            ..Default::default()
         })
     }
 }
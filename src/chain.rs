@@ -0,0 +1,213 @@
+#![allow(dead_code)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[cfg(feature = "native-sts")]
+use crate::credentials::Credentials;
+use crate::duration::{format_duration, parse_duration};
+#[cfg(feature = "native-sts")]
+use crate::sts::{AssumeRole, StsClient};
+
+/// The session name when a role sets no template
+pub const DEFAULT_SESSION_NAME: &str = "kee-{user}";
+
+/// The placeholders of session name templates
+pub const SESSION_NAME_PLACEHOLDERS: [&str; 5] = ["user", "alias", "profile", "account", "role"];
+
+/// STS caps role chaining sessions at an hour, see
+/// <https://docs.aws.amazon.com/IAM/latest/UserGuide/id_roles_terms-and-concepts.html#iam-term-role-chaining>
+pub const MIN_DURATION_SECS: u64 = 900;
+pub const MAX_DURATION_SECS: u64 = 3600;
+
+const MAX_SESSION_NAME_LEN: usize = 64;
+const MAX_TAGS: usize = 50;
+
+/// A role assumed with the credentials of the SSO role, or of the role
+/// before it in the chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct ChainedRole {
+    pub role_arn: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// Session name template, e.g. `{user}-deploy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_name: Option<String>,
+    /// Session length, e.g. `30m`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    /// Session tags
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl ChainedRole {
+    pub fn new(role_arn: &str) -> Self {
+        Self {
+            role_arn: role_arn.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The account and role name of `arn:aws:iam::ACCOUNT:role/[PATH/]NAME`
+    pub fn account_and_name(&self) -> Option<(&str, &str)> {
+        let mut parts = self.role_arn.splitn(6, ':');
+        if parts.next()? != "arn" || parts.nth(1)? != "iam" {
+            return None;
+        }
+        let account = parts.nth(1)?;
+        let resource = parts.next()?.strip_prefix("role/")?;
+        let name = resource.rsplit('/').next()?;
+        let valid =
+            account.len() == 12 && account.chars().all(|c| c.is_ascii_digit()) && !name.is_empty();
+        valid.then_some((account, name))
+    }
+
+    pub fn duration_secs(&self) -> Result<Option<u64>, String> {
+        let Some(duration) = &self.duration else {
+            return Ok(None);
+        };
+        let secs = parse_duration(duration)?.as_secs();
+        if !(MIN_DURATION_SECS..=MAX_DURATION_SECS).contains(&secs) {
+            return Err(format!(
+                "Invalid duration '{duration}' for {} (chained roles last 15m to 1h)",
+                self.role_arn
+            ));
+        }
+        Ok(Some(secs))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.account_and_name().is_none() {
+            return Err(format!(
+                "Invalid role ARN '{}' (expected arn:aws:iam::ACCOUNT_ID:role/NAME)",
+                self.role_arn
+            ));
+        }
+        if let Some(external_id) = &self.external_id {
+            if external_id.len() < 2 {
+                return Err(format!(
+                    "The external ID for {} is too short",
+                    self.role_arn
+                ));
+            }
+        }
+        if let Some(template) = &self.session_name {
+            validate_template(template)?;
+        }
+        self.duration_secs()?;
+        if self.tags.len() > MAX_TAGS {
+            return Err(format!(
+                "Too many session tags for {} (at most {MAX_TAGS})",
+                self.role_arn
+            ));
+        }
+        if self.tags.keys().any(|key| key.trim().is_empty()) {
+            return Err(format!("Empty session tag key for {}", self.role_arn));
+        }
+        Ok(())
+    }
+
+    /// The hop as `kee ls` shows it, e.g. `Deploy (222222222222), 1h, team=platform`
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match self.account_and_name() {
+            Some((account, name)) => format!("{name} ({account})"),
+            None => self.role_arn.clone(),
+        }];
+        if let Some(external_id) = &self.external_id {
+            parts.push(format!("external ID {external_id}"));
+        }
+        if let Some(template) = &self.session_name {
+            parts.push(format!("session {template}"));
+        }
+        if let Some(secs) = self.duration_secs().ok().flatten() {
+            parts.push(format_duration(std::time::Duration::from_secs(secs)));
+        }
+        parts.extend(
+            self.tags
+                .iter()
+                .map(|(key, value)| format!("{key}={value}")),
+        );
+        parts.join(", ")
+    }
+}
+
+fn placeholders(template: &str) -> Vec<&str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+        .collect()
+}
+
+pub fn validate_template(template: &str) -> Result<(), String> {
+    match placeholders(template)
+        .into_iter()
+        .find(|name| !SESSION_NAME_PLACEHOLDERS.contains(name))
+    {
+        Some(name) => Err(format!(
+            "Unknown placeholder {{{name}}} in session name '{template}' (use {})",
+            SESSION_NAME_PLACEHOLDERS
+                .iter()
+                .map(|name| format!("{{{name}}}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fill in a session name template and make it a valid `RoleSessionName`:
+/// characters STS rejects become `-`, and it's cut to 64 characters
+pub fn session_name(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut name = template.to_string();
+    for (placeholder, value) in vars {
+        name = name.replace(&format!("{{{placeholder}}}"), value);
+    }
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "+=,.@_-".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(MAX_SESSION_NAME_LEN)
+        .collect()
+}
+
+/// Assume each role of the chain in turn, starting with the SSO role's
+/// credentials, and return the last role's. `vars` fill in the session name
+/// templates, along with each role's account and name.
+#[cfg(feature = "native-sts")]
+pub fn assume_chain(
+    client: &StsClient,
+    credentials: Credentials,
+    roles: &[ChainedRole],
+    vars: &[(&str, &str)],
+) -> Result<Credentials, String> {
+    let mut credentials = credentials;
+    for role in roles {
+        role.validate()?;
+        let Some((account, name)) = role.account_and_name() else {
+            continue;
+        };
+        let mut vars = vars.to_vec();
+        vars.extend([("account", account), ("role", name)]);
+
+        let request = AssumeRole {
+            role_arn: role.role_arn.clone(),
+            session_name: session_name(
+                role.session_name.as_deref().unwrap_or(DEFAULT_SESSION_NAME),
+                &vars,
+            ),
+            external_id: role.external_id.clone(),
+            duration_secs: role.duration_secs()?,
+            tags: role.tags.clone().into_iter().collect(),
+        };
+        credentials = client
+            .assume_role(&credentials, &request)
+            .map_err(|e| format!("Could not assume {}: {e}", role.role_arn))?;
+    }
+    Ok(credentials)
+}
@@ -0,0 +1,86 @@
+#![allow(dead_code)]
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::credentials::Credentials;
+
+pub const FEDERATION_ENDPOINT: &str = "https://signin.aws.amazon.com/federation";
+pub const CONSOLE_URL: &str = "https://console.aws.amazon.com";
+const ISSUER: &str = "kee";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SigninTokenResponse {
+    signin_token: String,
+}
+
+/// Percent-encode everything but the RFC 3986 unreserved characters
+pub fn percent_encode(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                output.push(byte as char)
+            }
+            _ => output.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    output
+}
+
+/// The `Session` parameter of the `getSigninToken` federation call
+pub fn session_json(credentials: &Credentials) -> String {
+    json!({
+        "sessionId": credentials.access_key_id,
+        "sessionKey": credentials.secret_access_key,
+        "sessionToken": credentials.session_token.as_deref().unwrap_or_default(),
+    })
+    .to_string()
+}
+
+pub fn signin_token_url(endpoint: &str, credentials: &Credentials) -> String {
+    format!(
+        "{endpoint}?Action=getSigninToken&Session={}",
+        percent_encode(&session_json(credentials))
+    )
+}
+
+pub fn parse_signin_token(response: &str) -> Result<String, String> {
+    serde_json::from_str::<SigninTokenResponse>(response)
+        .map(|response| response.signin_token)
+        .map_err(|e| format!("Unexpected response from the federation endpoint: {e}"))
+}
+
+/// The console page to land on, e.g. the EC2 dashboard in a given region
+pub fn destination_url(service: Option<&str>, region: Option<&str>) -> String {
+    let mut url = format!("{CONSOLE_URL}/{}/home", service.unwrap_or("console"));
+    if let Some(region) = region {
+        url.push_str(&format!("?region={region}"));
+    }
+    url
+}
+
+pub fn login_url(endpoint: &str, signin_token: &str, destination: &str) -> String {
+    format!(
+        "{endpoint}?Action=login&Issuer={ISSUER}&Destination={}&SigninToken={}",
+        percent_encode(destination),
+        percent_encode(signin_token)
+    )
+}
+
+/// Exchange role credentials for a console sign-in URL
+pub fn signin_url(
+    endpoint: &str,
+    credentials: &Credentials,
+    destination: &str,
+) -> Result<String, String> {
+    let response = ureq::get(&signin_token_url(endpoint, credentials))
+        .call()
+        .map_err(|e| format!("Could not get a sign-in token: {e}"))?
+        .into_string()
+        .map_err(|e| format!("Could not read the sign-in token: {e}"))?;
+
+    let token = parse_signin_token(&response)?;
+    Ok(login_url(endpoint, &token, destination))
+}
@@ -0,0 +1,23 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Temporary role credentials, in the shape of the AWS `credential_process` output
+/// (which is also what `aws configure export-credentials --format process` prints)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+impl Credentials {
+    pub fn from_process_output(output: &str) -> Result<Self, String> {
+        serde_json::from_str(output).map_err(|e| format!("Unexpected credentials format: {e}"))
+    }
+}
@@ -0,0 +1,162 @@
+#![allow(dead_code)]
+
+use chrono::SecondsFormat;
+
+use crate::aws::ProfileInfo;
+use crate::credentials::Credentials;
+use crate::project::{quote, HookShell};
+
+pub const DOCKER: &str = "docker";
+
+/// The docker commands that start a process in a container and take `-e`
+const ENV_COMMANDS: [&str; 3] = ["run", "create", "exec"];
+
+/// The docker commands that choose the container's network
+const NETWORK_COMMANDS: [&str; 2] = ["run", "create"];
+
+/// Role credentials in the variables the AWS SDKs and CLI read
+pub fn credential_env(credentials: &Credentials) -> Vec<(&'static str, String)> {
+    let mut vars = vec![
+        ("AWS_ACCESS_KEY_ID", credentials.access_key_id.clone()),
+        (
+            "AWS_SECRET_ACCESS_KEY",
+            credentials.secret_access_key.clone(),
+        ),
+    ];
+    if let Some(token) = &credentials.session_token {
+        vars.push(("AWS_SESSION_TOKEN", token.clone()));
+    }
+    if let Some(expiration) = credentials.expiration {
+        vars.push((
+            "AWS_CREDENTIAL_EXPIRATION",
+            expiration.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+    }
+    vars
+}
+
+/// The profile's environment for a container, which has no AWS config to read
+/// the region from: the SSO region stands in when the profile has none
+pub fn container_env(profile: &ProfileInfo) -> Vec<(&str, &str)> {
+    let mut vars = Vec::new();
+    if profile.region.is_none() {
+        vars.push(("AWS_REGION", profile.sso_region.as_str()));
+        vars.push(("AWS_DEFAULT_REGION", profile.sso_region.as_str()));
+    }
+    vars.extend(profile.environment());
+    vars
+}
+
+/// The docker arguments with `-e NAME` for each variable after the `run`,
+/// `create` or `exec` command, and host networking for `host_network`.
+/// Only names are passed, so values never show up in the process list;
+/// docker reads them from its own environment.
+pub fn docker_args(
+    args: &[String],
+    names: &[&str],
+    host_network: bool,
+) -> Result<Vec<String>, String> {
+    let Some(index) = args
+        .iter()
+        .position(|arg| ENV_COMMANDS.contains(&arg.as_str()))
+    else {
+        return Err(format!(
+            "Expected a docker command ({}), e.g. kee docker PROFILE -- run IMAGE",
+            ENV_COMMANDS.join(", ")
+        ));
+    };
+    if host_network && !NETWORK_COMMANDS.contains(&args[index].as_str()) {
+        return Err(format!(
+            "--agent needs docker run or create: {} can't change the container's network",
+            args[index]
+        ));
+    }
+
+    let mut result = args[..=index].to_vec();
+    if host_network {
+        result.extend(["--network".to_string(), "host".to_string()]);
+    }
+    for name in names {
+        result.extend(["-e".to_string(), name.to_string()]);
+    }
+    result.extend_from_slice(&args[index + 1..]);
+    Ok(result)
+}
+
+/// Why `--agent` can't work with this docker, if it can't. With Docker Desktop,
+/// and any docker on macOS or Windows, containers run in a VM whose host
+/// network is not the host's, so they can't reach the agent on 127.0.0.1.
+/// `docker_os` is the daemon's `OperatingSystem`, as `docker info` reports it.
+pub fn host_network_unsupported(target_os: &str, docker_os: Option<&str>) -> Option<String> {
+    if target_os != "linux" {
+        return Some(format!(
+            "--agent needs Docker Engine on Linux: docker on {target_os} runs containers in a VM that can't reach the agent on 127.0.0.1"
+        ));
+    }
+    docker_os
+        .filter(|os| os.contains("Docker Desktop"))
+        .map(|_| {
+            "--agent doesn't work with Docker Desktop: its containers run in a VM that can't reach the agent on 127.0.0.1".to_string()
+        })
+}
+
+fn is_aws_config(source: &str) -> bool {
+    let source = source.trim_end_matches('/');
+    source == "~/.aws" || source.ends_with("/.aws") || source.contains("/.aws/")
+}
+
+/// Whether the arguments mount a host `~/.aws` directory (or a file in it)
+pub fn mounts_aws_config(args: &[String]) -> bool {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => match arg.strip_prefix("-v").filter(|rest| !rest.is_empty()) {
+                Some(rest) => ("-v", Some(rest.to_string())),
+                None => (arg.as_str(), None),
+            },
+        };
+        if !matches!(flag, "-v" | "--volume" | "--mount") {
+            continue;
+        }
+        let Some(value) = value.or_else(|| args.next().cloned()) else {
+            break;
+        };
+        let source = if flag == "--mount" {
+            value
+                .split(',')
+                .find_map(|field| {
+                    field
+                        .strip_prefix("source=")
+                        .or_else(|| field.strip_prefix("src="))
+                })
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            value.split(':').next().unwrap_or_default().to_string()
+        };
+        if is_aws_config(&source) {
+            return true;
+        }
+    }
+    false
+}
+
+/// The command line as it could be pasted into a shell
+pub fn command_line(args: &[String]) -> String {
+    let words: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@%+".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                quote(HookShell::Bash, arg)
+            }
+        })
+        .collect();
+    format!("{DOCKER} {}", words.join(" "))
+}
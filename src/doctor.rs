@@ -0,0 +1,195 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::aws::ProfileInfo;
+use crate::sso_cache::SsoToken;
+
+/// Sections of `~/.aws/config`, as returned by `Ini::get_map_ref`
+pub type AwsSections = HashMap<String, HashMap<String, Option<String>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A repair `kee doctor --fix` can apply without guessing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fix {
+    /// Re-create the `[profile]` section from the kee config
+    RestoreAwsProfile(String),
+    /// Re-create the `[sso-session]` section from the kee config
+    RestoreSsoSession(String),
+    /// Re-read the profile from `~/.aws/config` into the kee config
+    RefreshProfile(String),
+    ClearCurrentProfile,
+    RestrictPermissions(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub subject: String,
+    pub message: String,
+    pub remediation: String,
+    pub fix: Option<Fix>,
+}
+
+fn value<'a>(section: &'a HashMap<String, Option<String>>, key: &str) -> Option<&'a str> {
+    section.get(key).and_then(|value| value.as_deref())
+}
+
+pub fn check_profiles(
+    profiles: &HashMap<String, ProfileInfo>,
+    current_profile: Option<&str>,
+    aws: &AwsSections,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut aliases: Vec<&String> = profiles.keys().collect();
+    aliases.sort();
+
+    for alias in aliases {
+        let info = &profiles[alias];
+        let Some(section) = aws.get(&format!("profile {}", info.profile_name)) else {
+            findings.push(Finding {
+                severity: Severity::Error,
+                subject: alias.clone(),
+                message: format!(
+                    "The [profile {}] section is missing from ~/.aws/config",
+                    info.profile_name
+                ),
+                remediation: format!("Restore it with kee doctor --fix, or run kee rm {alias}"),
+                fix: Some(Fix::RestoreAwsProfile(alias.clone())),
+            });
+            continue;
+        };
+
+        if let Some(session) = value(section, "sso_session") {
+            if !aws.contains_key(&format!("sso-session {session}")) {
+                let restorable = !info.sso_start_url.is_empty() && !info.sso_region.is_empty();
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    subject: alias.clone(),
+                    message: format!("The profile references a missing [sso-session {session}]"),
+                    remediation: if restorable {
+                        "Restore it with kee doctor --fix".to_string()
+                    } else {
+                        format!("Run aws configure sso-session to create '{session}'")
+                    },
+                    fix: restorable.then(|| Fix::RestoreSsoSession(alias.clone())),
+                });
+            }
+        }
+
+        let account_id = value(section, "sso_account_id").unwrap_or_default();
+        let role_name = value(section, "sso_role_name").unwrap_or_default();
+        if account_id != info.sso_account_id || role_name != info.sso_role_name {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                subject: alias.clone(),
+                message: format!(
+                    "Kee has {}/{} but ~/.aws/config has {}/{}",
+                    info.sso_account_id, info.sso_role_name, account_id, role_name
+                ),
+                remediation: "Update kee from ~/.aws/config with kee doctor --fix".to_string(),
+                fix: Some(Fix::RefreshProfile(alias.clone())),
+            });
+        }
+    }
+
+    if let Some(current) = current_profile {
+        let known = profiles
+            .iter()
+            .any(|(alias, info)| alias == current || info.profile_name == current);
+        if !known {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                subject: current.to_string(),
+                message: "The current profile no longer exists".to_string(),
+                remediation: "Clear it with kee doctor --fix".to_string(),
+                fix: Some(Fix::ClearCurrentProfile),
+            });
+        }
+    }
+
+    findings
+}
+
+/// Check the output of `aws --version`, `None` meaning the binary wasn't found
+pub fn check_aws_cli(version_output: Option<&str>) -> Option<Finding> {
+    let Some(output) = version_output else {
+        return Some(Finding {
+            severity: Severity::Error,
+            subject: "aws".to_string(),
+            message: "The AWS CLI was not found".to_string(),
+            remediation: "Install AWS CLI v2 and make sure it's on your PATH".to_string(),
+            fix: None,
+        });
+    };
+
+    let major = output
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix("aws-cli/"))
+        .and_then(|version| version.split('.').next())
+        .and_then(|major| major.parse::<u32>().ok());
+
+    match major {
+        Some(major) if major >= 2 => None,
+        _ => Some(Finding {
+            severity: Severity::Error,
+            subject: "aws".to_string(),
+            message: format!("Kee needs AWS CLI v2, found: {}", output.trim()),
+            remediation: "Upgrade to AWS CLI v2".to_string(),
+            fix: None,
+        }),
+    }
+}
+
+/// Check a cached SSO token, `None` meaning there is no cache entry
+pub fn check_token(
+    subject: &str,
+    login_command: &str,
+    token: Option<&SsoToken>,
+    now: DateTime<Utc>,
+) -> Option<Finding> {
+    let (message, remediation) = match token {
+        None => (
+            "No cached SSO token".to_string(),
+            format!("Log in with {login_command}"),
+        ),
+        Some(token) if token.is_expired(now) => (
+            format!("The cached SSO token expired at {}", token.expires_at),
+            format!("Log in again with {login_command}"),
+        ),
+        Some(_) => return None,
+    };
+
+    Some(Finding {
+        severity: Severity::Warning,
+        subject: subject.to_string(),
+        message,
+        remediation,
+        fix: None,
+    })
+}
+
+/// Files holding configuration or tokens should only be readable by their owner
+pub fn check_permissions(path: &Path, mode: u32) -> Option<Finding> {
+    if mode & 0o077 == 0 {
+        return None;
+    }
+
+    Some(Finding {
+        severity: Severity::Warning,
+        subject: path.display().to_string(),
+        message: format!(
+            "The file is accessible by other users (mode {:o})",
+            mode & 0o777
+        ),
+        remediation: "Restrict it to mode 600 with kee doctor --fix".to_string(),
+        fix: Some(Fix::RestrictPermissions(path.to_path_buf())),
+    })
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+/// Parse a human friendly duration such as `90s`, `30m`, `8h` or `1h30m`.
+/// A bare number is read as minutes.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Duration cannot be empty".to_string());
+    }
+
+    let invalid = || format!("Invalid duration '{input}' (use e.g. 30m, 8h, 1h30m)");
+
+    if let Ok(minutes) = input.parse::<u64>() {
+        let secs = minutes.checked_mul(60).ok_or_else(invalid)?;
+        return non_zero(Duration::from_secs(secs), input);
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let multiplier = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!(
+            "Invalid duration '{input}' (missing unit after '{number}')"
+        ));
+    }
+
+    non_zero(Duration::from_secs(total), input)
+}
+
+fn non_zero(duration: Duration, input: &str) -> Result<Duration, String> {
+    if duration.is_zero() {
+        Err(format!("Duration '{input}' must be greater than zero"))
+    } else {
+        Ok(duration)
+    }
+}
+
+/// Format a duration the way `parse_duration` accepts it, e.g. `1h30m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return "0s".to_string();
+    }
+
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    let mut output = String::new();
+
+    if hours > 0 {
+        output.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
+        output.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 {
+        output.push_str(&format!("{seconds}s"));
+    }
+
+    output
+}
@@ -0,0 +1,126 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader};
+use std::time::Duration;
+
+use crate::aws::ProfileInfo;
+use crate::duration::format_duration;
+use crate::pattern;
+use crate::sso_cache;
+
+/// Profiles `kee each` runs at the same time unless `--jobs` says otherwise
+pub const DEFAULT_JOBS: usize = 4;
+
+/// The profiles to run for: every profile with `all`, otherwise those matching
+/// the names or patterns, narrowed to the ones carrying every tag.
+/// Returns the sorted aliases and the inputs that matched nothing.
+pub fn select_profiles<'a>(
+    profiles: &HashMap<String, ProfileInfo>,
+    all: bool,
+    tags: &[(String, String)],
+    patterns: &'a [String],
+) -> (Vec<String>, Vec<&'a str>) {
+    let mut names: Vec<String> = profiles.keys().cloned().collect();
+    names.sort();
+
+    let (mut selected, unmatched) = if all || patterns.is_empty() {
+        (names, Vec::new())
+    } else {
+        pattern::resolve(patterns, &names)
+    };
+
+    selected.retain(|alias| {
+        tags.iter()
+            .all(|(key, value)| profiles[alias].has_tag(key, value))
+    });
+    selected.sort();
+    (selected, unmatched)
+}
+
+/// Aliases grouped by the SSO token they need (the sso-session, or the start URL
+/// of legacy profiles), in order of first appearance, so each logs in only once
+pub fn login_groups(
+    profiles: &HashMap<String, ProfileInfo>,
+    aliases: &[String],
+) -> Vec<Vec<String>> {
+    let mut keys: Vec<String> = Vec::new();
+    let mut groups: Vec<Vec<String>> = Vec::new();
+
+    for alias in aliases {
+        let info = &profiles[alias];
+        let key = sso_cache::cache_key(&info.session_name, &info.sso_start_url);
+        match keys.iter().position(|existing| *existing == key) {
+            Some(index) => groups[index].push(alias.clone()),
+            None => {
+                keys.push(key);
+                groups.push(vec![alias.clone()]);
+            }
+        }
+    }
+    groups
+}
+
+/// A line of a command's output, labelled with the profile it ran for
+pub fn prefix_line(alias: &str, width: usize, line: &str) -> String {
+    format!("{alias:<width$} | {line}")
+}
+
+/// Call `emit` for each line of a command's output, without the line ending.
+/// Invalid UTF-8 is replaced rather than ending the stream, so the command never
+/// blocks on a full pipe.
+pub fn for_each_line(reader: impl io::Read, mut emit: impl FnMut(&str)) {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    while reader
+        .read_until(b'\n', &mut buffer)
+        .is_ok_and(|read| read > 0)
+    {
+        let line = String::from_utf8_lossy(&buffer);
+        emit(line.trim_end_matches(['\n', '\r']));
+        buffer.clear();
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Exited(i32),
+    /// Ended by a signal, so without an exit code
+    Killed,
+    /// Not run, with the reason
+    Skipped(String),
+}
+
+impl Outcome {
+    pub fn success(&self) -> bool {
+        *self == Outcome::Exited(0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EachResult {
+    pub alias: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+/// The summary table printed after all commands finished, one row per profile
+pub fn render_summary(results: &[EachResult]) -> String {
+    let width = results
+        .iter()
+        .map(|result| result.alias.len())
+        .chain(["PROFILE".len()])
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![format!(" {:<width$}  {:>4}  TIME", "PROFILE", "EXIT")];
+    for result in results {
+        let (exit, time) = match &result.outcome {
+            Outcome::Exited(code) => (code.to_string(), format_duration(result.duration)),
+            Outcome::Killed => ("-".to_string(), "killed".to_string()),
+            Outcome::Skipped(reason) => ("-".to_string(), reason.clone()),
+        };
+        lines.push(format!(" {:<width$}  {exit:>4}  {time}", result.alias));
+    }
+    lines.join("\n") + "\n"
+}
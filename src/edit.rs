@@ -0,0 +1,271 @@
+#![allow(dead_code)]
+
+use clap::Args;
+
+use crate::aws::{is_env_name, parse_env_var, parse_tag, ProfileInfo};
+use crate::chain::ChainedRole;
+use crate::duration::parse_duration;
+
+/// Output formats supported by the AWS CLI
+pub const OUTPUT_FORMATS: [&str; 5] = ["json", "yaml", "yaml-stream", "text", "table"];
+
+/// Field changes for `kee edit`
+#[derive(Args, Default, Debug)]
+pub struct ProfileChanges {
+    /// New AWS account ID
+    #[arg(long, value_name = "ACCOUNT_ID")]
+    pub account_id: Option<String>,
+    /// New SSO role name
+    #[arg(long, value_name = "ROLE_NAME")]
+    pub role: Option<String>,
+    /// New sso-session name
+    #[arg(long, value_name = "SESSION_NAME")]
+    pub sso_session: Option<String>,
+    /// New default session length (e.g. 8h)
+    #[arg(long, value_name = "DURATION", conflicts_with = "no_max_session")]
+    pub max_session: Option<String>,
+    /// Remove the default session length
+    #[arg(long)]
+    pub no_max_session: bool,
+    /// Add or replace a tag (repeatable)
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+    pub tags: Vec<(String, String)>,
+    /// Remove a tag (repeatable)
+    #[arg(long = "untag", value_name = "KEY")]
+    pub untags: Vec<String>,
+    /// Browser command for kee console, e.g. "firefox -P acme {url}"
+    #[arg(long, value_name = "COMMAND", conflicts_with = "no_browser")]
+    pub browser: Option<String>,
+    /// Use the system's default browser for kee console
+    #[arg(long)]
+    pub no_browser: bool,
+    /// Default region, e.g. eu-west-1
+    #[arg(long, value_name = "REGION", conflicts_with = "no_region")]
+    pub region: Option<String>,
+    /// Remove the default region
+    #[arg(long)]
+    pub no_region: bool,
+    /// Default AWS CLI output format
+    #[arg(long, value_name = "FORMAT", conflicts_with = "no_output")]
+    pub output: Option<String>,
+    /// Remove the default output format
+    #[arg(long)]
+    pub no_output: bool,
+    /// Set an environment variable (repeatable)
+    #[arg(long = "env", value_name = "NAME=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+    /// Remove an environment variable (repeatable)
+    #[arg(long = "unset-env", value_name = "NAME")]
+    pub unset_env: Vec<String>,
+    /// Command to run before a session starts
+    #[arg(long, value_name = "COMMAND", conflicts_with = "no_on_enter")]
+    pub on_enter: Option<String>,
+    /// Remove the on_enter hook
+    #[arg(long)]
+    pub no_on_enter: bool,
+    /// Command to run after a session ends
+    #[arg(long, value_name = "COMMAND", conflicts_with = "no_on_exit")]
+    pub on_exit: Option<String>,
+    /// Remove the on_exit hook
+    #[arg(long)]
+    pub no_on_exit: bool,
+    /// Assume a role after the SSO role and any already chained (repeatable)
+    #[arg(long = "assume-role", value_name = "ROLE_ARN")]
+    pub assume_roles: Vec<String>,
+    /// Remove the role chain (before adding any --assume-role)
+    #[arg(long)]
+    pub no_assume_role: bool,
+    /// External ID for the last role of the chain
+    #[arg(long, value_name = "ID")]
+    pub external_id: Option<String>,
+    /// Session name for the last role, e.g. "{user}-deploy"
+    #[arg(long, value_name = "TEMPLATE")]
+    pub role_session_name: Option<String>,
+    /// Session length for the last role (15m to 1h)
+    #[arg(long, value_name = "DURATION")]
+    pub role_duration: Option<String>,
+    /// Add or replace a session tag of the last role (repeatable)
+    #[arg(long = "session-tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+    pub session_tags: Vec<(String, String)>,
+}
+
+impl ProfileChanges {
+    pub fn is_empty(&self) -> bool {
+        self.account_id.is_none()
+            && self.role.is_none()
+            && self.sso_session.is_none()
+            && self.max_session.is_none()
+            && !self.no_max_session
+            && self.tags.is_empty()
+            && self.untags.is_empty()
+            && self.browser.is_none()
+            && !self.no_browser
+            && self.region.is_none()
+            && !self.no_region
+            && self.output.is_none()
+            && !self.no_output
+            && self.env.is_empty()
+            && self.unset_env.is_empty()
+            && self.on_enter.is_none()
+            && !self.no_on_enter
+            && self.on_exit.is_none()
+            && !self.no_on_exit
+            && self.assume_roles.is_empty()
+            && !self.no_assume_role
+            && !self.has_role_options()
+    }
+
+    /// Whether any option for the last role of the chain is given
+    pub fn has_role_options(&self) -> bool {
+        self.external_id.is_some()
+            || self.role_session_name.is_some()
+            || self.role_duration.is_some()
+            || !self.session_tags.is_empty()
+    }
+
+    pub fn apply(&self, profile: &mut ProfileInfo) {
+        if let Some(account_id) = &self.account_id {
+            profile.sso_account_id = account_id.clone();
+        }
+        if let Some(role) = &self.role {
+            profile.sso_role_name = role.clone();
+        }
+        if let Some(session) = &self.sso_session {
+            profile.session_name = session.clone();
+        }
+        if let Some(max_session) = &self.max_session {
+            profile.max_session = Some(max_session.clone());
+        }
+        if self.no_max_session {
+            profile.max_session = None;
+        }
+        for (key, value) in &self.tags {
+            profile.tags.insert(key.clone(), value.clone());
+        }
+        for key in &self.untags {
+            profile.tags.remove(key);
+        }
+        if let Some(browser) = &self.browser {
+            profile.browser = Some(browser.clone());
+        }
+        if self.no_browser {
+            profile.browser = None;
+        }
+        if let Some(region) = &self.region {
+            profile.region = Some(region.clone());
+        }
+        if self.no_region {
+            profile.region = None;
+        }
+        if let Some(output) = &self.output {
+            profile.output = Some(output.clone());
+        }
+        if self.no_output {
+            profile.output = None;
+        }
+        for (name, value) in &self.env {
+            profile.env.insert(name.clone(), value.clone());
+        }
+        for name in &self.unset_env {
+            profile.env.remove(name);
+        }
+        if let Some(on_enter) = &self.on_enter {
+            profile.on_enter = Some(on_enter.clone());
+        }
+        if self.no_on_enter {
+            profile.on_enter = None;
+        }
+        if let Some(on_exit) = &self.on_exit {
+            profile.on_exit = Some(on_exit.clone());
+        }
+        if self.no_on_exit {
+            profile.on_exit = None;
+        }
+        if self.no_assume_role {
+            profile.role_chain.clear();
+        }
+        profile
+            .role_chain
+            .extend(self.assume_roles.iter().map(|arn| ChainedRole::new(arn)));
+        if let Some(role) = profile.role_chain.last_mut() {
+            if let Some(external_id) = &self.external_id {
+                role.external_id = Some(external_id.clone());
+            }
+            if let Some(template) = &self.role_session_name {
+                role.session_name = Some(template.clone());
+            }
+            if let Some(duration) = &self.role_duration {
+                role.duration = Some(duration.clone());
+            }
+            for (key, value) in &self.session_tags {
+                role.tags.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+pub fn validate_profile(profile: &ProfileInfo) -> Result<(), String> {
+    if profile.sso_account_id.len() != 12
+        || !profile.sso_account_id.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(format!(
+            "Invalid account ID '{}' (expected 12 digits)",
+            profile.sso_account_id
+        ));
+    }
+
+    if profile.sso_role_name.trim().is_empty() {
+        return Err("The role name cannot be empty".to_string());
+    }
+
+    if profile.session_name.is_empty()
+        && (profile.sso_start_url.is_empty() || profile.sso_region.is_empty())
+    {
+        return Err("Either an sso-session or a start URL and region are required".to_string());
+    }
+
+    if let Some(max_session) = &profile.max_session {
+        parse_duration(max_session)?;
+    }
+
+    for (field, command) in [
+        ("browser", &profile.browser),
+        ("on_enter", &profile.on_enter),
+        ("on_exit", &profile.on_exit),
+    ] {
+        if command
+            .as_deref()
+            .is_some_and(|command| command.trim().is_empty())
+        {
+            return Err(format!("The {field} command cannot be empty"));
+        }
+    }
+
+    if let Some(output) = &profile.output {
+        if !OUTPUT_FORMATS.contains(&output.as_str()) {
+            return Err(format!(
+                "Invalid output format '{output}' (expected one of {})",
+                OUTPUT_FORMATS.join(", ")
+            ));
+        }
+    }
+
+    if let Some(name) = profile.env.keys().find(|name| !is_env_name(name)) {
+        return Err(format!("Invalid variable name '{name}'"));
+    }
+
+    for role in &profile.role_chain {
+        role.validate()?;
+    }
+
+    Ok(())
+}
+
+/// Render a profile for editing in `$EDITOR`
+pub fn render_toml(profile: &ProfileInfo) -> Result<String, String> {
+    toml::to_string_pretty(profile).map_err(|e| e.to_string())
+}
+
+pub fn parse_toml(content: &str) -> Result<ProfileInfo, String> {
+    toml::from_str(content).map_err(|e| e.message().to_string())
+}
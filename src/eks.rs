@@ -0,0 +1,168 @@
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+
+use crate::credentials::Credentials;
+use crate::sigv4::{self, Signer};
+
+/// EKS bearer tokens are this prefix and a base64url presigned STS URL, see
+/// <https://github.com/kubernetes-sigs/aws-iam-authenticator#api-authorization-from-outside-a-cluster>
+pub const TOKEN_PREFIX: &str = "k8s-aws-v1.";
+
+/// The header binding a token to one cluster
+pub const CLUSTER_ID_HEADER: &str = "x-k8s-aws-id";
+
+/// EKS accepts a token for 15 minutes; kubectl is told to renew it a minute early
+pub const TOKEN_MINUTES: i64 = 14;
+
+const URL_EXPIRES_SECS: u64 = 60;
+
+pub const EXEC_API_VERSION: &str = "client.authentication.k8s.io/v1beta1";
+
+pub const KUBECTL: &str = "kubectl";
+
+/// The regional STS endpoint
+pub fn sts_host(region: &str) -> String {
+    if region.starts_with("cn-") {
+        format!("sts.{region}.amazonaws.com.cn")
+    } else {
+        format!("sts.{region}.amazonaws.com")
+    }
+}
+
+/// A presigned STS `GetCallerIdentity` URL that EKS calls to learn who sent the token
+pub fn presigned_url(
+    credentials: &Credentials,
+    cluster: &str,
+    region: &str,
+    time: DateTime<Utc>,
+) -> String {
+    let host = sts_host(region);
+    Signer::new(credentials, region, "sts", time).presign_url(
+        "GET",
+        "/",
+        &[("Action", "GetCallerIdentity"), ("Version", "2011-06-15")],
+        &[("host", &host), (CLUSTER_ID_HEADER, cluster)],
+        URL_EXPIRES_SECS,
+        &sigv4::sha256_hex(b""),
+    )
+}
+
+/// The bearer token for a cluster, like `aws eks get-token` prints
+pub fn token(
+    credentials: &Credentials,
+    cluster: &str,
+    region: &str,
+    time: DateTime<Utc>,
+) -> String {
+    let url = presigned_url(credentials, cluster, region, time);
+    format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(url))
+}
+
+/// The `ExecCredential` kubectl reads from an exec credential plugin
+pub fn exec_credential(token: &str, time: DateTime<Utc>) -> String {
+    let expiration = time + chrono::Duration::minutes(TOKEN_MINUTES);
+    json!({
+        "kind": "ExecCredential",
+        "apiVersion": EXEC_API_VERSION,
+        "spec": {},
+        "status": {
+            "expirationTimestamp": expiration.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "token": token,
+        },
+    })
+    .to_string()
+}
+
+/// What kubeconfig needs from `aws eks describe-cluster`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Cluster {
+    pub name: String,
+    pub arn: String,
+    pub endpoint: String,
+    pub certificate_authority: CertificateAuthority,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct CertificateAuthority {
+    /// Base64 PEM
+    pub data: String,
+}
+
+impl Cluster {
+    pub fn from_json(output: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct DescribeCluster {
+            cluster: Cluster,
+        }
+        serde_json::from_str::<DescribeCluster>(output)
+            .map(|described| described.cluster)
+            .map_err(|e| format!("Unexpected describe-cluster output: {e}"))
+    }
+
+    /// The PEM certificate of the cluster's certificate authority
+    pub fn certificate(&self) -> Result<Vec<u8>, String> {
+        STANDARD
+            .decode(self.certificate_authority.data.trim())
+            .map_err(|e| format!("Invalid certificate authority data: {e}"))
+    }
+}
+
+/// The kubectl invocations that add a cluster, user and context called `name`,
+/// whose user runs `kee eks token` for the profile, and switch to the context
+pub fn kubeconfig_commands(
+    name: &str,
+    cluster: &Cluster,
+    certificate_file: &Path,
+    kee_bin: &str,
+    alias: &str,
+    region: &str,
+) -> Vec<Vec<String>> {
+    let token_args = [
+        "eks",
+        "token",
+        "--cluster",
+        &cluster.name,
+        "--region",
+        region,
+        alias,
+    ];
+    let mut credentials = vec![
+        "config".to_string(),
+        "set-credentials".to_string(),
+        name.to_string(),
+        format!("--exec-api-version={EXEC_API_VERSION}"),
+        format!("--exec-command={kee_bin}"),
+    ];
+    credentials.extend(token_args.iter().map(|arg| format!("--exec-arg={arg}")));
+
+    vec![
+        vec![
+            "config".to_string(),
+            "set-cluster".to_string(),
+            name.to_string(),
+            format!("--server={}", cluster.endpoint),
+            format!("--certificate-authority={}", certificate_file.display()),
+            "--embed-certs=true".to_string(),
+        ],
+        credentials,
+        vec![
+            "config".to_string(),
+            "set-context".to_string(),
+            name.to_string(),
+            format!("--cluster={name}"),
+            format!("--user={name}"),
+        ],
+        vec![
+            "config".to_string(),
+            "use-context".to_string(),
+            name.to_string(),
+        ],
+    ]
+}
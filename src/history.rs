@@ -0,0 +1,175 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::duration::parse_duration;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Use,
+    Exec,
+    Add,
+    Rm,
+    Login,
+    Logout,
+    Agent,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Use => "use",
+            Action::Exec => "exec",
+            Action::Add => "add",
+            Action::Rm => "rm",
+            Action::Login => "login",
+            Action::Logout => "logout",
+            Action::Agent => "agent",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    /// When the action started
+    pub timestamp: DateTime<Utc>,
+    pub action: Action,
+    pub profile: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(action: Action, profile: &str) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action,
+            profile: profile.to_string(),
+            account_id: None,
+            role: None,
+            duration_secs: None,
+            exit_status: None,
+            cwd: std::env::current_dir()
+                .ok()
+                .map(|dir| dir.display().to_string()),
+        }
+    }
+}
+
+/// Append-only JSONL log of everything kee did
+pub struct History {
+    file: PathBuf,
+}
+
+impl History {
+    pub fn new(file: PathBuf) -> Self {
+        Self { file }
+    }
+
+    /// Append an entry as a single write, so concurrent kee processes
+    /// don't interleave their lines
+    pub fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(entry)? + "\n";
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// Read all entries, skipping lines that can't be parsed
+    pub fn load(&self) -> io::Result<Vec<HistoryEntry>> {
+        if !self.file.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.file)?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct HistoryFilter {
+    pub profile: Option<String>,
+    pub action: Option<Action>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        if let Some(profile) = &self.profile {
+            if &entry.profile != profile {
+                return false;
+            }
+        }
+        if let Some(action) = self.action {
+            if entry.action != action {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            if entry.timestamp < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if entry.timestamp > until {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parse a point in time for filtering: RFC 3339, `YYYY-MM-DD`,
+/// `YYYY-MM-DD HH:MM` (local time) or a relative duration like `7d` (ago).
+pub fn parse_time(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    if let Ok(time) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return local_to_utc(time, input);
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return local_to_utc(date.and_hms_opt(0, 0, 0).unwrap(), input);
+    }
+
+    if let Ok(ago) = parse_duration(input) {
+        let ago = chrono::Duration::from_std(ago).map_err(|e| e.to_string())?;
+        return Ok(now - ago);
+    }
+
+    Err(format!(
+        "Invalid time '{input}' (use e.g. 2026-10-01, '2026-10-01 14:30' or 7d)"
+    ))
+}
+
+fn local_to_utc(time: NaiveDateTime, input: &str) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("Invalid local time '{input}'"))
+}
@@ -0,0 +1,74 @@
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use crate::aws::ProfileInfo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookEvent {
+    Enter,
+    Exit,
+}
+
+impl HookEvent {
+    /// The profile field and the file name in `~/.kee/hooks`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::Enter => "on_enter",
+            HookEvent::Exit => "on_exit",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hook {
+    /// An executable in `~/.kee/hooks`, shared by all profiles
+    Global(PathBuf),
+    /// A shell command from the profile's `on_enter`/`on_exit`
+    Profile(String),
+}
+
+/// The hooks to run for an event, in order. The global hook wraps the
+/// profile's: it runs first when entering and last when exiting.
+pub fn hooks_for(event: HookEvent, hooks_dir: &Path, profile: &ProfileInfo) -> Vec<Hook> {
+    let global = hooks_dir.join(event.name());
+    let global = global.is_file().then_some(Hook::Global(global));
+
+    let command = match event {
+        HookEvent::Enter => profile.on_enter.as_deref(),
+        HookEvent::Exit => profile.on_exit.as_deref(),
+    };
+    let own = command
+        .filter(|command| !command.trim().is_empty())
+        .map(|command| Hook::Profile(command.to_string()));
+
+    match event {
+        HookEvent::Enter => global.into_iter().chain(own).collect(),
+        HookEvent::Exit => own.into_iter().chain(global).collect(),
+    }
+}
+
+/// Profile metadata passed to hooks, on top of the profile's own environment
+pub fn hook_env(alias: &str, profile: &ProfileInfo, event: HookEvent) -> Vec<(String, String)> {
+    let mut vars = vec![("KEE_HOOK".to_string(), event.name().to_string())];
+    vars.extend(profile_env(alias, profile));
+    vars
+}
+
+/// Profile metadata for programs kee runs: hooks and plugins
+pub fn profile_env(alias: &str, profile: &ProfileInfo) -> Vec<(String, String)> {
+    let mut vars = vec![
+        ("KEE_PROFILE_ALIAS".to_string(), alias.to_string()),
+        ("KEE_ACCOUNT_ID".to_string(), profile.sso_account_id.clone()),
+        ("KEE_ROLE_NAME".to_string(), profile.sso_role_name.clone()),
+        ("KEE_SSO_SESSION".to_string(), profile.session_name.clone()),
+    ];
+
+    let tags: Vec<String> = profile
+        .tags
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    vars.push(("KEE_TAGS".to_string(), tags.join(",")));
+    vars
+}
@@ -0,0 +1,129 @@
+#![allow(dead_code)]
+
+use serde::Deserialize;
+
+use crate::aws::ProfileInfo;
+
+/// Output of `aws sts get-caller-identity`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct CallerIdentity {
+    pub user_id: String,
+    pub account: String,
+    pub arn: String,
+}
+
+impl CallerIdentity {
+    pub fn from_json(output: &str) -> Result<Self, String> {
+        serde_json::from_str(output).map_err(|e| format!("Unexpected caller identity: {e}"))
+    }
+
+    /// The IAM role and session name of an `assumed-role` ARN
+    pub fn assumed_role(&self) -> Option<(&str, &str)> {
+        let resource = self.arn.splitn(6, ':').nth(5)?;
+        let mut parts = resource.splitn(3, '/');
+        if parts.next()? != "assumed-role" {
+            return None;
+        }
+        Some((parts.next()?, parts.next()?))
+    }
+
+    /// The permission set name, for roles IAM Identity Center created
+    /// (`AWSReservedSSO_<permission set>_<hash>`), or the plain role name
+    pub fn sso_role_name(&self) -> Option<&str> {
+        let (role, _) = self.assumed_role()?;
+        Some(
+            role.strip_prefix("AWSReservedSSO_")
+                .and_then(|rest| rest.rsplit_once('_'))
+                .map_or(role, |(name, _)| name),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountList {
+    account_list: Vec<Account>,
+    #[serde(default)]
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    account_id: String,
+    account_name: String,
+}
+
+/// Find an account's name in a page of the portal's `ListAccounts` response.
+/// Returns the name, if found, and the token of the next page.
+pub fn account_name(
+    list_accounts_output: &str,
+    account_id: &str,
+) -> Result<(Option<String>, Option<String>), String> {
+    let list: AccountList = serde_json::from_str(list_accounts_output)
+        .map_err(|e| format!("Unexpected account list: {e}"))?;
+    let name = list
+        .account_list
+        .into_iter()
+        .find(|account| account.account_id == account_id)
+        .map(|account| account.account_name);
+    Ok((name, list.next_token))
+}
+
+/// Look up an account's name in the SSO portal with a cached access token.
+/// The token goes in a header rather than on an `aws` command line, where
+/// other users could see it.
+pub fn fetch_account_name(
+    region: &str,
+    access_token: &str,
+    account_id: &str,
+) -> Result<Option<String>, String> {
+    let url = format!("https://portal.sso.{region}.amazonaws.com/assignment/accounts");
+    let mut next_token: Option<String> = None;
+
+    loop {
+        let mut request = ureq::get(&url)
+            .set("x-amz-sso_bearer_token", access_token)
+            .query("max_result", "100");
+        if let Some(token) = &next_token {
+            request = request.query("next_token", token);
+        }
+
+        let response = request
+            .call()
+            .map_err(|e| format!("Could not list the SSO accounts: {e}"))?
+            .into_string()
+            .map_err(|e| format!("Could not read the SSO accounts: {e}"))?;
+
+        match account_name(&response, account_id)? {
+            (Some(name), _) => return Ok(Some(name)),
+            (None, Some(token)) => next_token = Some(token),
+            (None, None) => return Ok(None),
+        }
+    }
+}
+
+/// Differences between what STS reports and what the profile expects
+pub fn mismatches(profile: &ProfileInfo, identity: &CallerIdentity) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if identity.account != profile.sso_account_id {
+        mismatches.push(format!(
+            "STS reports account {}, but the profile has {}",
+            identity.account, profile.sso_account_id
+        ));
+    }
+
+    match identity.sso_role_name() {
+        Some(role) if role == profile.sso_role_name => {}
+        Some(role) => mismatches.push(format!(
+            "STS reports role {role}, but the profile has {}",
+            profile.sso_role_name
+        )),
+        None => mismatches.push(format!(
+            "STS reports {}, which is not an assumed role",
+            identity.arn
+        )),
+    }
+    mismatches
+}
@@ -2,8 +2,35 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod agent;
 mod aws;
-pub use aws::ProfileInfo;
+pub mod chain;
+pub mod console;
+pub mod credentials;
+pub mod docker;
+pub mod doctor;
+pub mod duration;
+pub mod each;
+pub mod edit;
+pub mod eks;
+pub mod history;
+pub mod hooks;
+pub mod identity;
+pub mod live;
+pub mod pattern;
+pub mod plugin;
+pub mod project;
+pub mod prompt;
+pub mod report;
+pub mod session;
+pub mod settings;
+pub mod shell;
+pub mod sigv4;
+pub mod sso_cache;
+#[cfg(feature = "native-sts")]
+pub mod sts;
+pub mod ui;
+pub use aws::{is_env_name, parse_env_var, parse_tag, unreferenced_sessions, ProfileInfo};
 
 pub const KEE_ART: &str = r#"
 
@@ -113,6 +140,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&profile).unwrap();
@@ -146,6 +174,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         config.add_profile("test".to_string(), profile.clone());
@@ -165,6 +194,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         config.add_profile("test".to_string(), profile.clone());
@@ -194,6 +224,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         config.add_profile("test".to_string(), profile.clone());
@@ -212,6 +243,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session1".to_string(),
+            ..Default::default()
         };
         let profile2 = ProfileInfo {
             profile_name: "kee-test2".to_string(),
@@ -220,6 +252,7 @@ mod tests {
             sso_account_id: "123456789013".to_string(),
             sso_role_name: "TestRole2".to_string(),
             session_name: "test-session2".to_string(),
+            ..Default::default()
         };
 
         config.add_profile("test1".to_string(), profile1);
@@ -254,6 +287,7 @@ mod tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         config.add_profile("test".to_string(), profile);
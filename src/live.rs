@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::aws::ProfileInfo;
+
+/// A running `kee use` sub-shell
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LiveSession {
+    pub pid: u32,
+    pub profile: String,
+    #[serde(default)]
+    pub sso_session: String,
+    #[serde(default)]
+    pub start_url: String,
+    pub started: DateTime<Utc>,
+    /// When the process started, as the system reports it, so a pid reused
+    /// by another process isn't mistaken for the sub-shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_start: Option<String>,
+}
+
+impl LiveSession {
+    pub fn new(pid: u32, profile: &ProfileInfo) -> Self {
+        Self {
+            pid,
+            profile: profile.profile_name.clone(),
+            sso_session: profile.session_name.clone(),
+            start_url: profile.sso_start_url.clone(),
+            started: Utc::now(),
+            process_start: process_start(pid),
+        }
+    }
+
+    /// Whether the registered process is still the sub-shell. Without a
+    /// recorded start time this can only be checked where none is available.
+    pub fn is_running(&self) -> bool {
+        match &self.process_start {
+            Some(start) => process_start(self.pid).as_ref() == Some(start),
+            None => cfg!(windows),
+        }
+    }
+
+    /// Whether the sub-shell depends on the given sso-session (or legacy start URL)
+    pub fn uses(&self, sso_session: &str, start_url: &str) -> bool {
+        if self.sso_session.is_empty() {
+            !start_url.is_empty() && self.start_url == start_url
+        } else {
+            self.sso_session == sso_session
+        }
+    }
+}
+
+/// One file per running sub-shell in `~/.kee/sessions`, so `kee logout` can end them
+pub struct LiveSessions {
+    dir: PathBuf,
+}
+
+impl LiveSessions {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, pid: u32) -> PathBuf {
+        self.dir.join(format!("{pid}.json"))
+    }
+
+    pub fn register(&self, session: &LiveSession) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.path(session.pid),
+            serde_json::to_string_pretty(session)?,
+        )
+    }
+
+    pub fn unregister(&self, pid: u32) {
+        let _ = fs::remove_file(self.path(pid));
+    }
+
+    /// All registered sub-shells, skipping unreadable files
+    pub fn list(&self) -> Vec<LiveSession> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut sessions: Vec<LiveSession> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| is_json(&entry.path()))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        sessions.sort_by_key(|session| session.pid);
+        sessions
+    }
+}
+
+/// The start time of a process: the `starttime` field of `/proc/PID/stat` on
+/// Linux, `ps -o lstart=` on other Unix systems. Nothing on Windows.
+pub fn process_start(pid: u32) -> Option<String> {
+    if cfg!(target_os = "linux") {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        return parse_stat_start_time(&stat);
+    }
+    if cfg!(windows) {
+        return None;
+    }
+
+    let output = Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let start = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !start.is_empty()).then_some(start)
+}
+
+/// The `starttime` of a `/proc/PID/stat` line. The command name may contain
+/// spaces and parentheses, so the fields are counted from its closing `)`.
+pub fn parse_stat_start_time(stat: &str) -> Option<String> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // After the name come the state (field 3) and so on up to starttime (22)
+    fields.split_whitespace().nth(19).map(str::to_string)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
@@ -1,17 +1,66 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::env::Shells;
+use clap_complete::{ArgValueCandidates, CompleteEnv, CompletionCandidate};
+use clap_complete_nushell::Nushell;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::ffi::OsString;
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
-use std::process::Command;
-
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod agent;
 mod aws;
-use aws::{AwsManager, ProfileInfo};
+mod chain;
+mod console;
+mod credentials;
+mod docker;
+mod doctor;
+mod duration;
+mod each;
+mod edit;
+mod eks;
+mod history;
+mod hooks;
+mod identity;
+mod live;
+mod pattern;
+mod plugin;
+mod project;
+mod prompt;
+mod report;
+mod session;
+mod settings;
+mod shell;
+mod sigv4;
+mod sso_cache;
+#[cfg(feature = "native-sts")]
+mod sts;
+mod ui;
+use aws::{parse_tag, AwsManager, ProfileInfo};
+use credentials::Credentials;
+use doctor::{Finding, Fix, Severity};
+use duration::{format_duration, parse_duration};
+use each::{EachResult, Outcome};
+use edit::ProfileChanges;
+use history::{Action, History, HistoryEntry, HistoryFilter};
+use hooks::{Hook, HookEvent};
+use identity::CallerIdentity;
+use live::{LiveSession, LiveSessions};
+use project::{HookShell, TrustStore};
+use prompt::PromptShell;
+use report::{GroupBy, ReportFormat};
+use session::SsoSession;
+use settings::{ColorChoice, Key, Settings, SettingsFile, SettingsStore};
+use shell::ShellKind;
+use ui::hlt;
 
-const BOLD_WHITE: &str = "\x1b[1;37m";
-const RESET: &str = "\x1b[0m";
 const KEE_ART: &str = r#"
 
  ██╗  ██╗███████╗███████╗
@@ -29,15 +78,26 @@ const KEE_CURRENT_PROFILE: &str = "KEE_CURRENT_PROFILE";
 const AWS_PROFILE: &str = "AWS_PROFILE";
 const AWS_CLI_AUTO_PROMPT: &str = "AWS_CLI_AUTO_PROMPT";
 const AWS_PAGER: &str = "AWS_PAGER";
+const KEE_CONSOLE_URL: &str = "KEE_CONSOLE_URL";
+const COMPLETE: &str = "COMPLETE";
+
+// Remaining session time at which the sub-shell gets a warning
+const SESSION_WARNINGS: [Duration; 2] = [Duration::from_secs(300), Duration::from_secs(60)];
+
+// How long a sub-shell that was hung up on gets to exit before it's killed
+const HANGUP_GRACE: Duration = Duration::from_secs(5);
 
 #[derive(Parser)]
 #[command(name = "kee")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(about = KEE_ART)]
-#[command(long_about = format!("{KEE_ART}\n\nExamples:\n  kee add myprofile          Add a new AWS profile\n  kee use myprofile          Use an available profile (starts sub-shell)\n  kee ls                     List all available profiles\n  kee current                Show current, active profile\n  kee rm myprofile           Remove a profile configuration"))]
+#[command(long_about = format!("{KEE_ART}\n\nExamples:\n  kee add myprofile          Add a new AWS profile\n  kee use myprofile          Use an available profile (starts sub-shell)\n  kee use myprofile --for 1h Use a profile for a limited time\n  kee ls                     List all available profiles\n  kee current                Show current, active profile\n  kee whoami                 Show the AWS identity of the active profile\n  kee session ls             List SSO sessions and their profiles\n  kee logout --all           Log out of SSO and end running sub-shells\n  kee rm myprofile           Remove a profile configuration\n  kee rm 'acme-*' --yes      Remove several profiles without asking\n  kee rename old new         Rename a profile\n  kee edit myprofile         Edit a profile in $EDITOR\n  kee edit myprofile --assume-role arn:aws:iam::222222222222:role/Deploy\n                             Assume a role on top of the SSO role\n  kee console myprofile      Open the AWS console for a profile\n  kee eks kubeconfig --cluster prod myprofile\n                             Use a profile for kubectl with an EKS cluster\n  kee agent myprofile        Serve auto-refreshed credentials on localhost\n  kee docker myprofile -- run --rm amazon/aws-cli s3 ls\n                             Run a container with the profile's credentials\n  kee each --tag env=prod -- aws s3 ls\n                             Run a command for every production profile\n  kee history --since 7d     Show what kee did in the last week\n  kee report --by tag        Total session time per client tag\n  kee doctor --fix           Find and repair configuration drift\n  kee trust                  Let the nearest .kee file select a profile\n  kee config set shell zsh   Change a setting in ~/.kee/settings.toml\n  kee prompt --shell bash    Print the prompt prefix of the active profile\n  kee completions zsh        Print the zsh completion script\n  kee foo                    Run the kee-foo plugin from PATH"))]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// When to use colors (overrides the color setting, NO_COLOR and CLICOLOR_FORCE)
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    color: Option<ColorChoice>,
 }
 
 #[derive(Subcommand)]
@@ -46,28 +106,383 @@ enum Commands {
     Add {
         #[arg(value_name = "PROFILE_NAME", help = "Name for the new AWS profile")]
         profile_name: String,
+        /// Default session length for this profile (e.g. 30m, 8h)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration_arg)]
+        max_session: Option<String>,
+        /// Label the profile, e.g. client=acme (repeatable)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
     },
     /// Use an available profile
     Use {
-        #[arg(value_name = "PROFILE_NAME", help = "Name of the AWS profile to use")]
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile to use",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
         profile_name: String,
+        /// End the sub-shell after this long (e.g. 30m, 1h30m)
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration_arg)]
+        duration: Option<String>,
+        /// Shell to start (overrides the shell setting)
+        #[arg(long, value_name = "PATH")]
+        shell: Option<String>,
     },
     /// List all available profiles
     Ls {
         /// Only show profile names (useful for scripting)
         #[arg(long)]
         names: bool,
+        /// Print the whole kee configuration as JSON (for plugins and scripts)
+        #[arg(long, conflicts_with = "names")]
+        json: bool,
     },
     /// Show current active profile
     Current,
-    /// Remove a profile
+    /// Show the AWS identity behind the active or named profile
+    Whoami {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile (defaults to the active one)",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: Option<String>,
+    },
+    /// Remove one or more profiles
+    Rm {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            required = true,
+            help = "Names or patterns (e.g. 'acme-*') of the AWS profiles to remove",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_names: Vec<String>,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Rename a profile
+    Rename {
+        #[arg(
+            value_name = "OLD_NAME",
+            help = "Current name of the profile",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        old_name: String,
+        #[arg(value_name = "NEW_NAME", help = "New name for the profile")]
+        new_name: String,
+    },
+    /// Edit a profile (opens $EDITOR when no field flags are given)
+    Edit {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile to edit",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: String,
+        #[command(flatten)]
+        changes: Box<ProfileChanges>,
+    },
+    /// Open the AWS console for a profile
+    Console {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile to sign in with",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: String,
+        /// Console service to open, e.g. ec2 or s3
+        #[arg(long)]
+        service: Option<String>,
+        /// Console region, e.g. eu-west-1
+        #[arg(long)]
+        region: Option<String>,
+        /// Print the sign-in link instead of opening it
+        #[arg(long)]
+        print: bool,
+    },
+    /// Serve a profile's credentials to SDKs and containers on localhost
+    Agent {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile to serve",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: String,
+        /// Port to listen on (default: any free port)
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Run docker with a profile's credentials in the container
+    Docker {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile whose credentials the container gets",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: String,
+        /// Point the container at a kee agent for auto-refreshed credentials (uses host networking, so Docker Engine on Linux only, not Docker Desktop)
+        #[arg(long)]
+        agent: bool,
+        /// Print the docker command line instead of running it
+        #[arg(long)]
+        dry_run: bool,
+        /// The docker command, after --, e.g. run --rm amazon/aws-cli s3 ls
+        #[arg(last = true, required = true, value_name = "DOCKER_ARGS")]
+        args: Vec<String>,
+    },
+    /// Run a command for several profiles in parallel
+    Each {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            required_unless_present_any = ["all", "tags"],
+            conflicts_with = "all",
+            help = "Names or patterns (e.g. 'acme-*') of the AWS profiles",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_names: Vec<String>,
+        /// Only profiles with this tag, e.g. env=prod (repeatable)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+        /// Run for every profile
+        #[arg(long)]
+        all: bool,
+        /// How many profiles to run at the same time
+        #[arg(
+            long,
+            short,
+            value_name = "N",
+            default_value_t = each::DEFAULT_JOBS,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=64)
+        )]
+        jobs: usize,
+        /// The command to run, after --
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Show the usage history
+    History {
+        /// Only show entries for this profile
+        #[arg(long, value_name = "PROFILE_NAME", add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+        /// Only show entries for this action
+        #[arg(long, value_enum)]
+        action: Option<Action>,
+        /// Only show entries after this time (e.g. 2026-10-01, 7d)
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+        /// Only show entries before this time
+        #[arg(long, value_name = "TIME")]
+        until: Option<String>,
+        /// Print the entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report active session time per account, tag or profile
+    Report {
+        /// Start of the reporting period (e.g. 2026-10-01, 30d)
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+        /// End of the reporting period
+        #[arg(long, value_name = "TIME")]
+        until: Option<String>,
+        /// What to total the time by
+        #[arg(long, value_enum, default_value_t = GroupBy::Account)]
+        by: GroupBy,
+        /// Tag key used with --by tag
+        #[arg(long, value_name = "KEY", default_value = "client")]
+        tag_key: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+    },
+    /// Check kee, the AWS config and the SSO token cache for problems
+    Doctor {
+        /// Repair what can be repaired safely
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Print the shell integration that selects profiles from .kee files
+    Hook {
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+    /// Allow a directory's .kee file to select its profile
+    Trust {
+        /// The .kee file or its directory (defaults to the nearest one)
+        path: Option<PathBuf>,
+        /// Stop trusting the file
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Log out of an SSO session and end the sub-shells using it
+    Logout {
+        #[arg(
+            value_name = "SESSION_NAME",
+            required_unless_present = "all",
+            conflicts_with = "all",
+            add = ArgValueCandidates::new(session_candidates)
+        )]
+        session: Option<String>,
+        /// Log out of every cached SSO session
+        #[arg(long)]
+        all: bool,
+    },
+    /// Manage SSO sessions
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Get EKS tokens and kubeconfig contexts for a profile
+    Eks {
+        #[command(subcommand)]
+        command: EksCommand,
+    },
+    /// Print the active profile's prompt prefix, for PROMPT_COMMAND, precmd or starship
+    Prompt {
+        /// Escape the colors for this shell's prompt
+        #[arg(long, value_enum, default_value_t = PromptShell::Plain)]
+        shell: PromptShell,
+        /// Template to use instead of the prompt setting
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+    },
+    /// Show or change the settings in ~/.kee/settings.toml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Print the shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// Print the environment changes for the current directory
+    #[command(hide = true)]
+    Export {
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+    /// Any other command runs the kee-NAME plugin on PATH
+    #[command(external_subcommand)]
+    Plugin(Vec<OsString>),
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// List SSO sessions and the profiles using them
+    Ls,
+    /// Create an SSO session
+    Add {
+        #[arg(value_name = "SESSION_NAME")]
+        name: String,
+        /// Start URL of the AWS access portal
+        #[arg(long, value_name = "URL")]
+        start_url: String,
+        /// Region of IAM Identity Center
+        #[arg(long)]
+        region: String,
+        /// Registration scopes
+        #[arg(long, default_value = session::DEFAULT_SCOPES)]
+        scopes: String,
+    },
+    /// Remove an SSO session no profile uses
     Rm {
+        #[arg(value_name = "SESSION_NAME", add = ArgValueCandidates::new(session_candidates))]
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Log in to an SSO session
+    Login {
+        #[arg(value_name = "SESSION_NAME", add = ArgValueCandidates::new(session_candidates))]
+        name: String,
+    },
+    /// Log out of a session (same as kee logout)
+    Logout {
+        #[arg(value_name = "SESSION_NAME", add = ArgValueCandidates::new(session_candidates))]
+        name: String,
+    },
+    /// Point a session and its profiles to a new start URL
+    SetUrl {
+        #[arg(value_name = "SESSION_NAME", add = ArgValueCandidates::new(session_candidates))]
+        name: String,
+        #[arg(value_name = "URL")]
+        url: String,
+        /// Also change the region
+        #[arg(long)]
+        region: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EksCommand {
+    /// Print a cluster's bearer token as an ExecCredential, for kubectl
+    Token {
+        #[arg(
+            value_name = "PROFILE_NAME",
+            help = "Name of the AWS profile to authenticate as",
+            add = ArgValueCandidates::new(profile_candidates)
+        )]
+        profile_name: String,
+        /// Name of the EKS cluster
+        #[arg(long)]
+        cluster: String,
+        /// Region of the cluster (default: the profile's region)
+        #[arg(long)]
+        region: Option<String>,
+    },
+    /// Add a kubeconfig context that gets its credentials from kee, and switch to it
+    Kubeconfig {
         #[arg(
             value_name = "PROFILE_NAME",
-            help = "Name of the AWS profile to remove"
+            help = "Name of the AWS profile to authenticate as",
+            add = ArgValueCandidates::new(profile_candidates)
         )]
         profile_name: String,
+        /// Name of the EKS cluster
+        #[arg(long)]
+        cluster: String,
+        /// Region of the cluster (default: the profile's region)
+        #[arg(long)]
+        region: Option<String>,
+        /// Name of the context (default: PROFILE_NAME/CLUSTER)
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// List all settings with their values and where they come from
+    List,
+    /// Print the effective value of a setting
+    Get {
+        #[arg(value_enum)]
+        key: Key,
+    },
+    /// Change a setting in the settings file
+    Set {
+        #[arg(value_enum)]
+        key: Key,
+        value: String,
     },
+    /// Remove a setting from the settings file, restoring its default
+    Unset {
+        #[arg(value_enum)]
+        key: Key,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+    Nushell,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -79,21 +494,247 @@ struct KeeConfig {
 struct KeeManager {
     config_file: PathBuf,
     aws_manager: AwsManager,
+    history: History,
+    hooks_dir: PathBuf,
+    trust_store: TrustStore,
+    live_sessions: LiveSessions,
+    settings_store: SettingsStore,
+    settings: Settings,
+}
+
+/// The CLI, with the plugins on PATH listed in the top-level help
+fn cli_command() -> clap::Command {
+    let command = Cli::command();
+    // Only search PATH when the help will be shown
+    let first = env::args_os().nth(1);
+    if !matches!(
+        first.as_ref().and_then(|arg| arg.to_str()),
+        None | Some("help" | "-h" | "--help")
+    ) {
+        return command;
+    }
+
+    let mut plugins = plugin::find_plugins(&env::var_os("PATH").unwrap_or_default());
+    plugins.retain(|name, _| command.find_subcommand(name).is_none());
+    if plugins.is_empty() {
+        return command;
+    }
+    command.after_help(plugin::render_plugins(&plugins))
+}
+
+/// The shell used to run command strings such as hooks and browser commands
+fn shell_invocation() -> (&'static str, &'static str) {
+    if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    }
+}
+
+/// Profile names for shell completion, described by account ID and role
+fn profile_candidates() -> Vec<CompletionCandidate> {
+    // Completion runs on every <Tab>, so it only reads the config: no ~/.kee
+    // to create and no settings warnings in the middle of the command line
+    let Some(home_dir) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    let config = read_config(&home_dir.join(".kee").join("config.json"));
+
+    let mut profiles: Vec<(String, ProfileInfo)> = config.profiles.into_iter().collect();
+    profiles.sort_by(|a, b| a.0.cmp(&b.0));
+    profiles
+        .into_iter()
+        .map(|(alias, info)| {
+            let help = format!("{} {}", info.sso_account_id, info.sso_role_name);
+            CompletionCandidate::new(alias).help(Some(help.into()))
+        })
+        .collect()
+}
+
+/// The kee config at `path`, or an empty one if it's missing or unreadable
+fn read_config(path: &Path) -> KeeConfig {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => KeeConfig::default(),
+    }
+}
+
+/// SSO session names for shell completion, described by their start URL
+fn session_candidates() -> Vec<CompletionCandidate> {
+    let Ok(aws_config) = AwsManager::new().and_then(|aws| aws.load_config()) else {
+        return Vec::new();
+    };
+
+    session::read_sessions(aws_config.get_map_ref())
+        .into_iter()
+        .map(|session| CompletionCandidate::new(session.name).help(Some(session.start_url.into())))
+        .collect()
+}
+
+/// Print the script registering kee's completions. Except for nushell, the script
+/// calls back into kee so profile names are always up to date.
+fn print_completions(shell: CompletionShell) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let name = match shell {
+        CompletionShell::Bash => "bash",
+        CompletionShell::Zsh => "zsh",
+        CompletionShell::Fish => "fish",
+        CompletionShell::Powershell => "powershell",
+        CompletionShell::Elvish => "elvish",
+        CompletionShell::Nushell => {
+            clap_complete::generate(Nushell, &mut Cli::command(), "kee", &mut stdout);
+            return Ok(());
+        }
+    };
+
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, name))?;
+    let kee = env::current_exe()?;
+    completer.write_registration(COMPLETE, "kee", "kee", &kee.to_string_lossy(), &mut stdout)
+}
+
+/// Report the requests a kee agent refused or could not answer
+fn log_agent_request(request: &agent::Request, response: &agent::Response) {
+    if let Some(message) = response.error_message() {
+        eprintln!(
+            " [!] {} {}: {} {message}",
+            request.method, request.path, response.status
+        );
+    }
+}
+
+/// The profile's role credentials through the AWS CLI, which refreshes them from
+/// the SSO token when needed
+fn export_credentials(aws_bin: &str, profile_name: &str) -> Option<Credentials> {
+    let output = Command::new(aws_bin)
+        .args([
+            "configure",
+            "export-credentials",
+            "--profile",
+            profile_name,
+            "--format",
+            "process",
+        ])
+        .env(AWS_CLI_AUTO_PROMPT, "off")
+        .env(AWS_PAGER, "")
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    Credentials::from_process_output(&String::from_utf8_lossy(&output.stdout)).ok()
+}
+
+/// The SSO portal endpoint the environment names instead of the regional one
+fn portal_override() -> Option<String> {
+    env::var(sso_cache::PORTAL_ENDPOINT_VAR)
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+}
+
+/// The profile's role credentials straight from the SSO cache and portal,
+/// without the AWS CLI. `None` when the cache has no usable SSO token, so
+/// only the CLI can tell; an error is the portal's final answer.
+#[cfg(feature = "native-sts")]
+fn native_credentials(
+    sso_cache_dir: &Path,
+    cli_cache_dir: &Path,
+    profile_info: &ProfileInfo,
+) -> Option<Result<Credentials, String>> {
+    let portal = portal_override();
+    sso_cache::resolve_role_credentials(
+        sso_cache_dir,
+        cli_cache_dir,
+        profile_info,
+        portal.as_deref(),
+        chrono::Utc::now(),
+    )
+    .transpose()
+}
+
+#[cfg(not(feature = "native-sts"))]
+fn native_credentials(_: &Path, _: &Path, _: &ProfileInfo) -> Option<Result<Credentials, String>> {
+    None
+}
+
+/// An STS client for the profile's region, or for the endpoint the environment names
+#[cfg(feature = "native-sts")]
+fn sts_client(profile_info: &ProfileInfo) -> sts::StsClient {
+    let region = profile_info
+        .region
+        .as_deref()
+        .unwrap_or(&profile_info.sso_region);
+    match env::var(sts::ENDPOINT_VAR) {
+        Ok(endpoint) if !endpoint.is_empty() => sts::StsClient::with_endpoint(&endpoint, region),
+        _ => sts::StsClient::new(region),
+    }
+}
+
+/// The credentials of the profile's last chained role, assumed with the SSO
+/// role's credentials; those themselves for profiles without a role chain
+#[cfg(feature = "native-sts")]
+fn chain_credentials(
+    credentials: Credentials,
+    alias: &str,
+    profile_info: &ProfileInfo,
+) -> Result<Credentials, String> {
+    if profile_info.role_chain.is_empty() {
+        return Ok(credentials);
+    }
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| "kee".to_string());
+    chain::assume_chain(
+        &sts_client(profile_info),
+        credentials,
+        &profile_info.role_chain,
+        &[
+            ("user", &user),
+            ("alias", alias),
+            ("profile", &profile_info.profile_name),
+        ],
+    )
 }
 
-fn hlt(text: &str) -> String {
-    format!("{BOLD_WHITE}{text}{RESET}")
+#[cfg(not(feature = "native-sts"))]
+fn chain_credentials(
+    credentials: Credentials,
+    _: &str,
+    profile_info: &ProfileInfo,
+) -> Result<Credentials, String> {
+    if profile_info.role_chain.is_empty() {
+        return Ok(credentials);
+    }
+    Err("Role chains need kee built with the native-sts feature".to_string())
+}
+
+fn parse_duration_arg(value: &str) -> Result<String, String> {
+    parse_duration(value).map(|_| value.to_string())
 }
 
 impl KeeManager {
+    /// Ask a yes/no question; Enter alone gives the confirm_default setting
+    /// Ask on stderr, so the question shows even when the output is piped
     fn prompt_user(&self, message: &str) -> io::Result<bool> {
-        print!("{message}");
-        io::stdout().flush()?;
+        if self.settings.confirm_default {
+            eprint!("{}", message.replace("(y/N)", "(Y/n)"));
+        } else {
+            eprint!("{message}");
+        }
+        io::stderr().flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
 
-        Ok(input.trim().to_lowercase() == "y")
+        Ok(match input.trim().to_lowercase().as_str() {
+            "" => self.settings.confirm_default,
+            answer => answer == "y",
+        })
     }
 
     fn new() -> io::Result<Self> {
@@ -111,22 +752,58 @@ impl KeeManager {
         fs::create_dir_all(&config_dir)?;
 
         let aws_manager = AwsManager::new()?;
+        let history = History::new(config_dir.join("history.jsonl"));
+        let hooks_dir = config_dir.join("hooks");
+        let trust_store = TrustStore::new(config_dir.join("trusted.json"));
+        let live_sessions = LiveSessions::new(config_dir.join("sessions"));
+
+        // Broken settings must not lock users out of kee, so they only cause warnings
+        let settings_store = SettingsStore::new(config_dir.join("settings.toml"));
+        let settings_file = settings_store.load().unwrap_or_else(|e| {
+            eprintln!(" [!] Ignoring the settings file: {e}");
+            SettingsFile::default()
+        });
+        let (settings, warnings) = Settings::resolve(&settings_file, |name| env::var(name).ok());
+        for warning in warnings {
+            eprintln!(" [!] {warning}");
+        }
 
         Ok(Self {
             config_file,
             aws_manager,
+            history,
+            hooks_dir,
+            trust_store,
+            live_sessions,
+            settings_store,
+            settings,
         })
     }
 
-    fn load_config(&self) -> KeeConfig {
-        if !self.config_file.exists() {
-            return KeeConfig::default();
+    fn record(&self, entry: HistoryEntry) {
+        if let Err(e) = self.history.append(&entry) {
+            eprintln!(" [!] Could not write to the usage history: {e}");
         }
+    }
 
-        match fs::read_to_string(&self.config_file) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => KeeConfig::default(),
+    fn record_profile(
+        &self,
+        action: Action,
+        profile_name: &str,
+        profile_info: Option<&ProfileInfo>,
+        exit_status: Option<i32>,
+    ) {
+        let mut entry = HistoryEntry::new(action, profile_name);
+        if let Some(info) = profile_info {
+            entry.account_id = Some(info.sso_account_id.clone());
+            entry.role = Some(info.sso_role_name.clone());
         }
+        entry.exit_status = exit_status;
+        self.record(entry);
+    }
+
+    fn load_config(&self) -> KeeConfig {
+        read_config(&self.config_file)
     }
 
     fn save_config(&self, config: &KeeConfig) -> io::Result<()> {
@@ -134,7 +811,12 @@ impl KeeManager {
         fs::write(&self.config_file, content)
     }
 
-    fn add_profile(&self, profile_name: &str) -> io::Result<bool> {
+    fn add_profile(
+        &self,
+        profile_name: &str,
+        max_session: Option<String>,
+        tags: BTreeMap<String, String>,
+    ) -> io::Result<bool> {
         println!("\n Starting SSO configuration...");
         println!(" (This will open your browser to complete authentication.)");
         println!("\n Follow the prompts:");
@@ -157,12 +839,13 @@ impl KeeManager {
         );
 
         // Run aws configure sso
-        let status = Command::new("aws")
+        let status = Command::new(&self.settings.aws_bin)
             .args(["configure", "sso", "--profile", profile_name])
             .status()?;
 
         if !status.success() {
-            println!(" [X] SSO configuration failed.");
+            self.record_profile(Action::Add, profile_name, None, status.code());
+            eprintln!(" [X] SSO configuration failed.");
             return Ok(false);
         }
 
@@ -177,15 +860,20 @@ impl KeeManager {
 
         // Read profile info
         let profile_info = match self.aws_manager.read_profile(profile_name) {
-            Some(info) => info,
+            Some(info) => ProfileInfo {
+                max_session,
+                tags,
+                ..info
+            },
             None => {
-                println!("\n [X] Could not read profile information.");
+                eprintln!("\n [X] Could not read profile information.");
                 return Ok(false);
             }
         };
 
         // Save to kee config
         let mut config = self.load_config();
+        self.record_profile(Action::Add, profile_name, Some(&profile_info), Some(0));
         config
             .profiles
             .insert(profile_name.to_string(), profile_info);
@@ -195,29 +883,34 @@ impl KeeManager {
         if self.check_credentials(profile_name) {
             println!("\n [✓] The profile was added and it's working!");
         } else {
-            println!("\n [X] I created the profile but credentials may need a refresh...");
-            println!(" {} aws sso login --profile {}", hlt("Try:"), profile_name);
+            eprintln!("\n [X] I created the profile but credentials may need a refresh...");
+            eprintln!(" {} aws sso login --profile {}", hlt("Try:"), profile_name);
         }
 
         Ok(true)
     }
 
-    fn list_profiles(&self, names: bool) {
+    fn list_profiles(&self, names: bool, json: bool) -> io::Result<()> {
         let config = self.load_config();
 
+        if json {
+            println!("{}", serde_json::to_string_pretty(&config)?);
+            return Ok(());
+        }
+
         if config.profiles.is_empty() {
-            println!(
+            eprintln!(
                 "\n [!] No profiles configured.\n Run {} to add one.",
                 hlt("kee add PROFILE_NAME")
             );
-            return;
+            return Ok(());
         }
 
         if names {
             for profile_name in config.profiles.keys() {
                 println!("{profile_name}");
             }
-            return;
+            return Ok(());
         }
 
         println!();
@@ -230,214 +923,2441 @@ impl KeeManager {
 
             println!(" {}{}", hlt(profile_name), status);
             println!(" • {} {}", hlt("Account ID:"), profile_info.sso_account_id);
-            println!(" • {} {}\n", hlt("Role:"), profile_info.sso_role_name);
+            println!(" • {} {}", hlt("Role:"), profile_info.sso_role_name);
+            if !profile_info.role_chain.is_empty() {
+                println!(" • {}", hlt("Role chain:"));
+                println!(
+                    "   {} ({})",
+                    profile_info.sso_role_name, profile_info.sso_account_id
+                );
+                for role in &profile_info.role_chain {
+                    println!("   → {}", role.summary());
+                }
+            }
+            if let Some(max_session) = &profile_info.max_session {
+                println!(" • {} {}", hlt("Max session:"), max_session);
+            }
+            if !profile_info.tags.is_empty() {
+                let tags: Vec<String> = profile_info
+                    .tags
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect();
+                println!(" • {} {}", hlt("Tags:"), tags.join(", "));
+            }
+            if let Some(region) = &profile_info.region {
+                println!(" • {} {}", hlt("Region:"), region);
+            }
+            if let Some(output) = &profile_info.output {
+                println!(" • {} {}", hlt("Output:"), output);
+            }
+            if !profile_info.env.is_empty() {
+                let vars: Vec<String> = profile_info
+                    .env
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+                println!(" • {} {}", hlt("Env:"), vars.join(", "));
+            }
+            if let Some(browser) = &profile_info.browser {
+                println!(" • {} {}", hlt("Browser:"), browser);
+            }
+            if let Some(on_enter) = &profile_info.on_enter {
+                println!(" • {} {}", hlt("On enter:"), on_enter);
+            }
+            if let Some(on_exit) = &profile_info.on_exit {
+                println!(" • {} {}", hlt("On exit:"), on_exit);
+            }
+            println!();
         }
+        Ok(())
     }
 
-    fn remove_profile(&self, profile_name: &str) -> io::Result<bool> {
+    fn remove_profiles(&self, patterns: &[String], yes: bool) -> io::Result<bool> {
         let mut config = self.load_config();
 
-        if !config.profiles.contains_key(profile_name) {
-            println!("\n [!] Profile '{}' not found.", hlt(profile_name));
+        let mut names: Vec<String> = config.profiles.keys().cloned().collect();
+        names.sort();
+        let (targets, unmatched) = pattern::resolve(patterns, &names);
+
+        for name in unmatched {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(name));
+        }
+        if targets.is_empty() {
             return Ok(false);
         }
 
-        // Confirm removal
-        if !self.prompt_user(&format!(
-            "\n [!] Are you sure you want to remove profile '{}'? (y/N): ",
-            hlt(profile_name)
-        ))? {
+        // Confirm removal, once for all targets
+        let question = match targets.as_slice() {
+            [profile_name] => format!(
+                "\n [!] Are you sure you want to remove profile '{}'? (y/N): ",
+                hlt(profile_name)
+            ),
+            _ => {
+                let list: Vec<String> = targets
+                    .iter()
+                    .map(|name| format!(" • {}", hlt(name)))
+                    .collect();
+                format!(
+                    "\n{}\n\n [!] Are you sure you want to remove these {} profiles? (y/N): ",
+                    list.join("\n"),
+                    targets.len()
+                )
+            }
+        };
+        if !yes && !self.prompt_user(&question)? {
             return Ok(false);
         }
 
-        // Get profile info before removal
-        let profile_info = config.profiles.get(profile_name).unwrap().clone();
-
-        // Remove from config
-        config.profiles.remove(profile_name);
+        let mut removed = Vec::new();
+        for profile_name in &targets {
+            let profile_info = config.profiles.remove(profile_name).unwrap();
 
-        // Clear current profile if it's the one being removed
-        if config.current_profile.as_deref() == Some(profile_name) {
-            config.current_profile = None;
+            // Clear current profile if it's the one being removed
+            if config.current_profile.as_deref() == Some(profile_name) {
+                config.current_profile = None;
+            }
+            removed.push((profile_name, profile_info));
         }
 
         self.save_config(&config)?;
 
-        // Remove the AWS profile from config file
-        let hlt_profile = hlt(profile_name);
-        match self.aws_manager.remove_profile(&profile_info.profile_name) {
-            Ok(_) => {
-                println!(" [✓] Profile '{hlt_profile}' has been removed.");
-            }
-            Err(e) => {
-                println!(" [✓] Profile '{hlt_profile}' removed from {}.", hlt("Kee"));
-                println!(
-                    " [!] Could not remove AWS profile '{}': {}",
-                    hlt(&profile_info.profile_name),
-                    e
-                );
-                println!(
-                    " You may want to remove it manually from {}",
-                    hlt("~/.aws/config")
-                );
+        println!();
+        for (profile_name, profile_info) in &removed {
+            self.record_profile(Action::Rm, profile_name, Some(profile_info), None);
+
+            // Remove the AWS profile from config file
+            let hlt_profile = hlt(profile_name);
+            match self.aws_manager.remove_profile(&profile_info.profile_name) {
+                Ok(_) => {
+                    println!(" [✓] Profile '{hlt_profile}' has been removed.");
+                }
+                Err(e) => {
+                    println!(" [✓] Profile '{hlt_profile}' removed from {}.", hlt("Kee"));
+                    eprintln!(
+                        " [!] Could not remove AWS profile '{}': {}",
+                        hlt(&profile_info.profile_name),
+                        e
+                    );
+                    eprintln!(
+                        " You may want to remove it manually from {}",
+                        hlt("~/.aws/config")
+                    );
+                }
             }
         }
 
+        let removed: Vec<ProfileInfo> = removed.into_iter().map(|(_, info)| info).collect();
+        self.remove_orphans(&removed, &config, yes)?;
+
         Ok(true)
     }
 
-    fn use_profile(&self, profile_name: &str) -> io::Result<bool> {
-        // Check if already in a Kee profile
-        if env::var(KEE_ACTIVE_PROFILE).is_ok() {
-            let current_profile =
-                env::var(KEE_CURRENT_PROFILE).unwrap_or_else(|_| "unknown".to_string());
-            println!(
-                "\n [!] You are using a {} profile: {}",
-                hlt("Kee"),
-                hlt(&current_profile)
-            );
-            println!(" Exit the current session first by typing {}", hlt("exit"));
-            return Ok(false);
-        }
-
-        let mut config = self.load_config();
-        let hlt_profile = hlt(profile_name);
-
-        if !config.profiles.contains_key(profile_name) {
-            println!("\n [!] Profile '{hlt_profile}' not found.");
+    /// Offer to remove sso-sessions and cached tokens nothing refers to any more
+    fn remove_orphans(
+        &self,
+        removed: &[ProfileInfo],
+        config: &KeeConfig,
+        yes: bool,
+    ) -> io::Result<()> {
+        let aws_config = self.aws_manager.load_config()?;
+        let sessions = aws::unreferenced_sessions(
+            removed.iter().map(|info| info.session_name.as_str()),
+            aws_config.get_map_ref(),
+        );
 
-            if !config.profiles.is_empty() {
-                println!(" Available profiles:");
-                for name in config.profiles.keys() {
-                    println!(" • {}\n", hlt(name));
-                }
+        let mut sections = Vec::new();
+        let mut tokens = Vec::new();
+        for session in &sessions {
+            let section_name = format!("sso-session {session}");
+            if aws_config.get_map_ref().contains_key(&section_name) {
+                sections.push(section_name);
             }
+        }
 
-            // Offer to add the profile
-            if self.prompt_user(" Would you like to add now? (y/N): ")? {
-                if self.add_profile(profile_name)? {
-                    if self.prompt_user(&format!(
-                        " Would you like to use profile '{hlt_profile}' now? (y/N): "
-                    ))? {
-                        // Reload config
-                        config = self.load_config();
-                    } else {
-                        println!(
-                            "\n Profile '{}' is ready to use. Run {} when needed.",
-                            hlt_profile,
-                            hlt(&format!("kee use {profile_name}"))
-                        );
-                        return Ok(true);
-                    }
-                } else {
-                    println!(" [X] Failed to add profile '{hlt_profile}'.");
-                    return Ok(false);
-                }
+        for info in removed {
+            let orphaned = if info.session_name.is_empty() {
+                !config.profiles.values().any(|other| {
+                    other.session_name.is_empty() && other.sso_start_url == info.sso_start_url
+                })
             } else {
-                return Ok(false);
+                sessions.contains(&info.session_name)
+            };
+
+            let path = sso_cache::token_path(self.aws_manager.sso_cache_dir(), info);
+            if orphaned && path.exists() && !tokens.contains(&path) {
+                tokens.push(path);
             }
         }
 
-        let profile_info = config.profiles.get(profile_name).unwrap();
-        let profile_name = &profile_info.profile_name;
-
-        // Check credentials
-        if !self.check_credentials(profile_name) {
-            println!("\n [!] Credentials expired or not available. Attempting SSO login...");
-            if !self.sso_login(profile_name)? {
-                println!(
-                    " [X] Failed to authenticate. Please run {} manually.",
-                    hlt("aws sso login")
-                );
-                return Ok(false);
-            }
+        if sections.is_empty() && tokens.is_empty() {
+            return Ok(());
         }
 
-        // Update current profile
-        config.current_profile = Some(profile_name.to_string());
-        self.save_config(&config)?;
+        println!("\n The following are no longer used by any profile:");
+        for section_name in &sections {
+            println!(" • [{}] in {}", hlt(section_name), hlt("~/.aws/config"));
+        }
+        for path in &tokens {
+            println!(" • Cached SSO token {}", hlt(&path.display().to_string()));
+        }
 
-        // Start subshell
-        self.start_subshell(profile_name)?;
+        if !yes && !self.prompt_user("\n Remove them as well? (y/N): ")? {
+            return Ok(());
+        }
 
-        // Clear current profile when subshell exits
-        config.current_profile = None;
-        self.save_config(&config)?;
+        if !sections.is_empty() {
+            self.aws_manager.remove_sections(&sections)?;
+        }
+        for path in &tokens {
+            fs::remove_file(path)?;
+        }
+        println!(" [✓] Cleaned up unused SSO configuration.");
 
-        Ok(true)
+        Ok(())
     }
 
-    fn current_profile(&self) {
+    fn rename_profile(&self, old_name: &str, new_name: &str) -> io::Result<bool> {
+        let mut config = self.load_config();
+
+        if !config.profiles.contains_key(old_name) {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(old_name));
+            return Ok(false);
+        }
+
+        if config.profiles.contains_key(new_name)
+            || self
+                .aws_manager
+                .has_section(&format!("profile {new_name}"))?
+        {
+            eprintln!("\n [!] Profile '{}' already exists.", hlt(new_name));
+            return Ok(false);
+        }
+
+        if env::var(KEE_CURRENT_PROFILE).as_deref() == Ok(old_name) {
+            eprintln!(
+                "\n [!] Profile '{}' is in use. Exit the current session first by typing {}",
+                hlt(old_name),
+                hlt("exit")
+            );
+            return Ok(false);
+        }
+
+        let mut profile_info = config.profiles.remove(old_name).unwrap();
+        let old_aws_name = std::mem::replace(&mut profile_info.profile_name, new_name.to_string());
+
+        // Rename the AWS section first, so kee never points at a missing profile
+        if !self.aws_manager.rename_section(
+            &format!("profile {old_aws_name}"),
+            &format!("profile {new_name}"),
+        )? {
+            eprintln!(
+                "\n [!] No [profile {old_aws_name}] section found in {}. Run {} to restore it.",
+                hlt("~/.aws/config"),
+                hlt("kee doctor --fix")
+            );
+        }
+
+        let current = config.current_profile.as_deref();
+        if current == Some(old_name) || current == Some(old_aws_name.as_str()) {
+            config.current_profile = Some(new_name.to_string());
+        }
+
+        config.profiles.insert(new_name.to_string(), profile_info);
+        self.save_config(&config)?;
+
+        println!(
+            "\n [✓] Profile '{}' has been renamed to '{}'.",
+            hlt(old_name),
+            hlt(new_name)
+        );
+        Ok(true)
+    }
+
+    fn edit_profile(&self, profile_name: &str, changes: &ProfileChanges) -> io::Result<bool> {
+        let mut config = self.load_config();
+
+        let Some(original) = config.profiles.get(profile_name).cloned() else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(profile_name));
+            return Ok(false);
+        };
+
+        let mut updated = original.clone();
+        if changes.is_empty() {
+            match self.edit_in_editor(profile_name, &original)? {
+                Some(edited) => updated = edited,
+                None => return Ok(false),
+            }
+        } else {
+            changes.apply(&mut updated);
+            if changes.has_role_options() && updated.role_chain.is_empty() {
+                eprintln!(
+                    "\n [X] There is no role to set options for. Add one with {}",
+                    hlt("--assume-role ROLE_ARN")
+                );
+                return Ok(false);
+            }
+        }
+
+        if let Err(e) = edit::validate_profile(&updated) {
+            eprintln!("\n [X] {e}");
+            return Ok(false);
+        }
+
+        if updated.profile_name != original.profile_name {
+            eprintln!(
+                "\n [X] The profile name can't be edited. Use {} instead.",
+                hlt("kee rename")
+            );
+            return Ok(false);
+        }
+
+        if updated == original {
+            eprintln!("\n [!] Nothing to change.");
+            return Ok(false);
+        }
+
+        if !updated.session_name.is_empty()
+            && !self
+                .aws_manager
+                .has_section(&format!("sso-session {}", updated.session_name))?
+        {
+            eprintln!(
+                "\n [X] There is no [sso-session {}] in {}.",
+                updated.session_name,
+                hlt("~/.aws/config")
+            );
+            return Ok(false);
+        }
+
+        self.aws_manager.write_profile(&updated)?;
+
+        // Pick up the start URL and region of a (possibly new) sso-session
+        if let Some(fresh) = self.aws_manager.read_profile(&updated.profile_name) {
+            updated.update_sso_details(fresh);
+        }
+
+        config.profiles.insert(profile_name.to_string(), updated);
+        self.save_config(&config)?;
+
+        println!("\n [✓] Profile '{}' has been updated.", hlt(profile_name));
+        Ok(true)
+    }
+
+    fn edit_in_editor(
+        &self,
+        profile_name: &str,
+        profile_info: &ProfileInfo,
+    ) -> io::Result<Option<ProfileInfo>> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| {
+                if cfg!(windows) {
+                    "notepad".to_string()
+                } else {
+                    "vi".to_string()
+                }
+            });
+        let rendered = edit::render_toml(profile_info)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let file = tempfile::Builder::new()
+            .prefix("kee-edit-")
+            .suffix(".toml")
+            .tempfile()?;
+        let path = file.path();
+        fs::write(
+            path,
+            format!(
+                "# Editing profile '{profile_name}'. Save and close to apply, or clear the file to cancel.\n{rendered}"
+            ),
+        )?;
+
+        let result = loop {
+            // The editor may come with arguments, e.g. "code --wait"
+            let mut parts = editor.split_whitespace();
+            let program = parts.next().unwrap_or("vi");
+            let status = Command::new(program).args(parts).arg(path).status()?;
+            if !status.success() {
+                break None;
+            }
+
+            let content = fs::read_to_string(path)?;
+            let is_blank = content
+                .lines()
+                .all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'));
+            if is_blank {
+                break None;
+            }
+
+            match edit::parse_toml(&content)
+                .and_then(|edited| edit::validate_profile(&edited).map(|_| edited))
+            {
+                Ok(edited) => break Some(edited),
+                Err(e) => {
+                    eprintln!("\n [X] {e}");
+                    if !self.prompt_user(" Edit again? (y/N): ")? {
+                        break None;
+                    }
+                }
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn use_profile(&self, profile_name: &str, duration: Option<&str>) -> io::Result<bool> {
+        // Check if already in a Kee profile
+        if env::var(KEE_ACTIVE_PROFILE).is_ok() {
+            let current_profile =
+                env::var(KEE_CURRENT_PROFILE).unwrap_or_else(|_| "unknown".to_string());
+            eprintln!(
+                "\n [!] You are using a {} profile: {}",
+                hlt("Kee"),
+                hlt(&current_profile)
+            );
+            eprintln!(" Exit the current session first by typing {}", hlt("exit"));
+            return Ok(false);
+        }
+
+        let mut config = self.load_config();
+        let hlt_profile = hlt(profile_name);
+
+        if !config.profiles.contains_key(profile_name) {
+            eprintln!("\n [!] Profile '{hlt_profile}' not found.");
+
+            if !config.profiles.is_empty() {
+                eprintln!(" Available profiles:");
+                for name in config.profiles.keys() {
+                    eprintln!(" • {}\n", hlt(name));
+                }
+            }
+
+            // Offer to add the profile
+            if self.prompt_user(" Would you like to add now? (y/N): ")? {
+                if self.add_profile(profile_name, None, BTreeMap::new())? {
+                    if self.prompt_user(&format!(
+                        " Would you like to use profile '{hlt_profile}' now? (y/N): "
+                    ))? {
+                        // Reload config
+                        config = self.load_config();
+                    } else {
+                        println!(
+                            "\n Profile '{}' is ready to use. Run {} when needed.",
+                            hlt_profile,
+                            hlt(&format!("kee use {profile_name}"))
+                        );
+                        return Ok(true);
+                    }
+                } else {
+                    eprintln!(" [X] Failed to add profile '{hlt_profile}'.");
+                    return Ok(false);
+                }
+            } else {
+                return Ok(false);
+            }
+        }
+
+        let alias = profile_name;
+        let profile_info = config.profiles.get(alias).unwrap().clone();
+        let profile_name = &profile_info.profile_name;
+
+        // An explicit --for wins over the profile's max_session
+        let timeout = match duration.or(profile_info.max_session.as_deref()) {
+            Some(value) => match parse_duration(value) {
+                Ok(timeout) => Some(timeout),
+                Err(e) => {
+                    eprintln!("\n [X] {e}");
+                    return Ok(false);
+                }
+            },
+            None => None,
+        };
+
+        // Check credentials
+        if !self.check_credentials(profile_name) && !self.login(alias, &profile_info)? {
+            return Ok(false);
+        }
+
+        // The sub-shell of a profile with a role chain gets the last role's credentials
+        let chained = if profile_info.role_chain.is_empty() {
+            None
+        } else {
+            match self.role_credentials(alias, &profile_info)? {
+                Some(credentials) => Some(credentials),
+                None => {
+                    eprintln!(" [X] The session was not started.");
+                    return Ok(false);
+                }
+            }
+        };
+
+        if !self.run_hooks(HookEvent::Enter, alias, &profile_info, &[]) {
+            eprintln!(" [X] The session was not started.");
+            return Ok(false);
+        }
+
+        // Update current profile
+        config.current_profile = Some(profile_name.to_string());
+        self.save_config(&config)?;
+
+        // Start subshell
+        let mut entry = HistoryEntry::new(Action::Use, alias);
+        entry.account_id = Some(profile_info.sso_account_id.clone());
+        entry.role = Some(profile_info.sso_role_name.clone());
+        let started = Instant::now();
+
+        entry.exit_status = self.start_subshell(alias, &profile_info, timeout, chained.as_ref())?;
+        entry.duration_secs = Some(started.elapsed().as_secs());
+
+        let exit_env = [
+            (
+                "KEE_EXIT_STATUS",
+                entry
+                    .exit_status
+                    .map(|status| status.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "KEE_SESSION_SECONDS",
+                entry.duration_secs.unwrap_or_default().to_string(),
+            ),
+        ];
+        self.record(entry);
+        self.run_hooks(HookEvent::Exit, alias, &profile_info, &exit_env);
+
+        // Clear current profile when subshell exits
+        config.current_profile = None;
+        self.save_config(&config)?;
+
+        Ok(true)
+    }
+
+    fn current_profile(&self) {
         // Check if in active session
         if let Ok(current) = env::var(KEE_CURRENT_PROFILE) {
             println!("\n Current profile: {}", hlt(&current));
             println!(" Type {} to return to your main shell.", hlt("exit"));
         } else {
-            let config = self.load_config();
-            match config.current_profile {
-                Some(current) => println!("\n Current profile: {}", hlt(&current)),
-                None => println!("\n [!] No profile is currently active."),
+            let config = self.load_config();
+            match config.current_profile {
+                Some(current) => println!("\n Current profile: {}", hlt(&current)),
+                None => eprintln!("\n [!] No profile is currently active."),
+            }
+        }
+    }
+
+    fn list_sessions(&self) -> io::Result<()> {
+        let config = self.load_config();
+        let aws_config = self.aws_manager.load_config()?;
+        let sessions = session::read_sessions(aws_config.get_map_ref());
+
+        // Sessions kee profiles refer to that ~/.aws/config no longer has
+        let mut missing: Vec<&str> = config
+            .profiles
+            .values()
+            .map(|info| info.session_name.as_str())
+            .filter(|name| !name.is_empty() && !sessions.iter().any(|s| s.name == *name))
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        if sessions.is_empty() && missing.is_empty() {
+            eprintln!("\n [!] No SSO sessions configured.");
+            eprintln!(
+                " Add one with {}",
+                hlt("kee session add NAME --start-url URL --region REGION")
+            );
+            return Ok(());
+        }
+
+        let now = chrono::Utc::now();
+        for sso_session in &sessions {
+            println!("\n {}", hlt(&sso_session.name));
+            println!(" • {} {}", hlt("Start URL:"), sso_session.start_url);
+            println!(" • {} {}", hlt("Region:"), sso_session.region);
+
+            let path =
+                sso_cache::session_token_path(self.aws_manager.sso_cache_dir(), &sso_session.name);
+            let token = match sso_cache::read_token(&path) {
+                Ok(token) if token.is_expired(now) => "expired".to_string(),
+                Ok(token) => format!(
+                    "valid until {}",
+                    token
+                        .expires_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                ),
+                Err(_) => "not logged in".to_string(),
+            };
+            println!(" • {} {}", hlt("Token:"), token);
+
+            let profiles = session::dependent_profiles(&config.profiles, &sso_session.name);
+            if !profiles.is_empty() {
+                println!(" • {} {}", hlt("Profiles:"), profiles.join(", "));
+            }
+        }
+
+        for name in missing {
+            println!("\n {} [!] missing from ~/.aws/config", hlt(name));
+            let profiles = session::dependent_profiles(&config.profiles, name);
+            println!(" • {} {}", hlt("Profiles:"), profiles.join(", "));
+        }
+        println!();
+
+        Ok(())
+    }
+
+    fn add_session(
+        &self,
+        name: &str,
+        start_url: &str,
+        region: &str,
+        scopes: &str,
+    ) -> io::Result<bool> {
+        if let Err(e) = session::validate_session_name(name)
+            .and_then(|_| session::validate_start_url(start_url))
+        {
+            eprintln!("\n [X] {e}");
+            return Ok(false);
+        }
+
+        let sso_session = SsoSession {
+            name: name.to_string(),
+            start_url: start_url.to_string(),
+            region: region.to_string(),
+            scopes: Some(scopes.to_string()),
+        };
+        if self.aws_manager.has_section(&sso_session.section_name())? {
+            eprintln!("\n [!] Session '{}' already exists.", hlt(name));
+            return Ok(false);
+        }
+
+        self.aws_manager
+            .set_section(&sso_session.section_name(), &sso_session.section_values())?;
+        println!("\n [✓] Session '{}' added.", hlt(name));
+        println!(
+            " Log in with {}, then add profiles with {}",
+            hlt(&format!("kee session login {name}")),
+            hlt("kee add PROFILE_NAME")
+        );
+        Ok(true)
+    }
+
+    fn remove_session(&self, name: &str, yes: bool) -> io::Result<bool> {
+        let section_name = format!("sso-session {name}");
+        if !self.aws_manager.has_section(&section_name)? {
+            eprintln!("\n [!] Session '{}' not found.", hlt(name));
+            return Ok(false);
+        }
+
+        let profiles = session::dependent_profiles(&self.load_config().profiles, name);
+        if !profiles.is_empty() {
+            eprintln!(
+                "\n [X] Session '{}' is used by: {}",
+                hlt(name),
+                profiles.join(", ")
+            );
+            eprintln!(" Remove or edit those profiles first.");
+            return Ok(false);
+        }
+
+        if !yes && !self.prompt_user(&format!("\n Remove session '{}'? (y/N): ", hlt(name)))? {
+            return Ok(false);
+        }
+
+        self.aws_manager.remove_sections(&[section_name])?;
+        let path = sso_cache::session_token_path(self.aws_manager.sso_cache_dir(), name);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        println!(" [✓] Session '{}' removed.", hlt(name));
+        Ok(true)
+    }
+
+    fn session_login(&self, name: &str) -> io::Result<bool> {
+        if !self
+            .aws_manager
+            .has_section(&format!("sso-session {name}"))?
+        {
+            eprintln!("\n [!] Session '{}' not found.", hlt(name));
+            return Ok(false);
+        }
+
+        let status = Command::new(&self.settings.aws_bin)
+            .args(["sso", "login", "--sso-session", name])
+            .status()?;
+        self.record_profile(
+            Action::Login,
+            name,
+            None,
+            Some(if status.success() { 0 } else { 1 }),
+        );
+
+        if !status.success() {
+            eprintln!(" [X] Failed to log in to session '{}'.", hlt(name));
+        }
+        Ok(status.success())
+    }
+
+    /// Revoke the cached SSO token of a session (or of every session), delete
+    /// the role credentials cached with it and end the sub-shells using it
+    fn logout(&self, session: Option<&str>) -> io::Result<bool> {
+        let cache_dir = self.aws_manager.sso_cache_dir();
+        let tokens = match session {
+            Some(name) => {
+                let path = sso_cache::session_token_path(cache_dir, name);
+                match sso_cache::read_token(&path) {
+                    Ok(token) => vec![(path, token)],
+                    Err(_) => Vec::new(),
+                }
+            }
+            None => sso_cache::cached_tokens(cache_dir),
+        };
+        let live: Vec<LiveSession> = self
+            .live_sessions
+            .list()
+            .into_iter()
+            .filter(|live| session.is_none_or(|name| live.uses(name, "")))
+            .collect();
+
+        if tokens.is_empty() && live.is_empty() {
+            match session {
+                Some(name) => eprintln!("\n [!] Session '{}' is not logged in.", hlt(name)),
+                None => eprintln!("\n [!] No SSO session is logged in."),
+            }
+            return Ok(false);
+        }
+
+        // Every file that can be deleted is, even after one failed
+        let mut failed = false;
+        let mut remove = |path: &Path| match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                eprintln!("\n [X] Could not delete {}: {e}", path.display());
+                failed = true;
+            }
+            _ => {}
+        };
+
+        let now = chrono::Utc::now();
+        let portal = portal_override();
+        for (path, token) in &tokens {
+            if let (false, Some(region)) = (token.is_expired(now), &token.region) {
+                let endpoint = sso_cache::resolve_portal_endpoint(region, portal.as_deref());
+                if let Err(e) = sso_cache::portal_logout(&endpoint, &token.access_token) {
+                    eprintln!("\n [!] {e}");
+                }
+            }
+            remove(path);
+        }
+
+        let cli_cache_dir = self.aws_manager.cli_cache_dir();
+        let role_caches = match session {
+            Some(name) => self
+                .load_config()
+                .profiles
+                .values()
+                .filter(|info| info.session_name == name)
+                .map(|info| sso_cache::role_cache_path(cli_cache_dir, info))
+                .filter(|path| path.exists())
+                .collect(),
+            None => sso_cache::role_credential_caches(cli_cache_dir),
+        };
+        for path in &role_caches {
+            remove(path);
+        }
+
+        for live in &live {
+            self.end_live_session(live);
+        }
+
+        let name = session.unwrap_or("all");
+        self.record_profile(Action::Logout, name, None, Some(i32::from(failed)));
+
+        match session {
+            Some(name) => println!("\n [✓] Logged out of session '{}'.", hlt(name)),
+            None => println!(
+                "\n [✓] Logged out of {} SSO session(s).",
+                hlt(&tokens.len().to_string())
+            ),
+        }
+        if !role_caches.is_empty() {
+            println!(" • Deleted {} cached role credential(s)", role_caches.len());
+        }
+        if !live.is_empty() {
+            let profiles: Vec<&str> = live.iter().map(|live| live.profile.as_str()).collect();
+            println!(" • Ended the sub-shells of: {}", profiles.join(", "));
+        }
+        if failed {
+            eprintln!("\n [X] Some cached files could not be deleted.");
+        }
+        Ok(!failed)
+    }
+
+    /// Terminate a sub-shell; its `kee use` then cleans up and records the session
+    fn end_live_session(&self, live: &LiveSession) {
+        if !live.is_running() {
+            // The sub-shell is gone, e.g. after a crash, and its pid may be reused
+            self.live_sessions.unregister(live.pid);
+            return;
+        }
+
+        if !hang_up(live.pid) {
+            self.live_sessions.unregister(live.pid);
+        }
+    }
+
+    /// Re-point a session to a new start URL, in `~/.aws/config` and every dependent profile
+    fn set_session_url(&self, name: &str, url: &str, region: Option<&str>) -> io::Result<bool> {
+        if let Err(e) = session::validate_start_url(url) {
+            eprintln!("\n [X] {e}");
+            return Ok(false);
+        }
+
+        let section_name = format!("sso-session {name}");
+        let mut aws_config = self.aws_manager.load_config()?;
+        if !aws_config.get_map_ref().contains_key(&section_name) {
+            eprintln!("\n [!] Session '{}' not found.", hlt(name));
+            return Ok(false);
+        }
+
+        let mut values = vec![("sso_start_url", url)];
+        if let Some(region) = region {
+            values.push(("sso_region", region));
+        }
+
+        let copies = session::profile_sections_with_copies(aws_config.get_map_ref(), name);
+        for section in std::iter::once(&section_name).chain(&copies) {
+            for (key, value) in &values {
+                if section == &section_name || aws_config.get(section, key).is_some() {
+                    aws_config.set(section, key, Some(value.to_string()));
+                }
+            }
+        }
+        self.aws_manager.save_config(&aws_config)?;
+
+        let mut config = self.load_config();
+        let profiles = session::dependent_profiles(&config.profiles, name);
+        for alias in &profiles {
+            if let Some(info) = config.profiles.get_mut(alias) {
+                info.sso_start_url = url.to_string();
+                if let Some(region) = region {
+                    info.sso_region = region.to_string();
+                }
+            }
+        }
+        self.save_config(&config)?;
+
+        println!("\n [✓] Session '{}' now points to {}.", hlt(name), hlt(url));
+        if !profiles.is_empty() {
+            println!(" Updated profiles: {}", profiles.join(", "));
+        }
+        println!(
+            " Log in again with {}",
+            hlt(&format!("kee session login {name}"))
+        );
+        Ok(true)
+    }
+
+    fn whoami(&self, profile_name: Option<&str>) -> io::Result<bool> {
+        let config = self.load_config();
+        let wanted = profile_name
+            .map(str::to_string)
+            .or_else(|| env::var(KEE_CURRENT_PROFILE).ok())
+            .or(config.current_profile.clone());
+        let Some(wanted) = wanted else {
+            eprintln!(
+                "\n [!] No profile is currently active. Run {}",
+                hlt("kee whoami PROFILE_NAME")
+            );
+            return Ok(false);
+        };
+
+        // The active profile is tracked by its AWS profile name, which may differ from the alias
+        let Some((alias, profile_info)) = config
+            .profiles
+            .iter()
+            .find(|(alias, info)| **alias == wanted || info.profile_name == wanted)
+        else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(&wanted));
+            return Ok(false);
+        };
+
+        let Some(identity) = self.caller_identity(&profile_info.profile_name) else {
+            eprintln!(
+                "\n [!] Credentials for '{}' expired or not available. Run {} to log in.",
+                hlt(alias),
+                hlt(&format!("kee use {alias}"))
+            );
+            return Ok(false);
+        };
+
+        println!("\n {}", hlt(alias));
+        println!(" • {} {}", hlt("ARN:"), identity.arn);
+        println!(" • {} {}", hlt("Account ID:"), identity.account);
+        if let Some(name) = self.account_name(profile_info, &identity.account) {
+            println!(" • {} {}", hlt("Account name:"), name);
+        }
+        if let Some(role) = identity.sso_role_name() {
+            println!(" • {} {}", hlt("Role:"), role);
+        }
+        if let Some((_, session)) = identity.assumed_role() {
+            println!(" • {} {}", hlt("Session:"), session);
+        }
+        if !profile_info.sso_start_url.is_empty() {
+            println!(" • {} {}", hlt("Start URL:"), profile_info.sso_start_url);
+        }
+        let expiration = self
+            .export_credentials(&profile_info.profile_name)
+            .and_then(|credentials| credentials.expiration);
+        if let Some(expiration) = expiration {
+            let remaining = (expiration - chrono::Utc::now())
+                .to_std()
+                .map_or("expired".to_string(), |left| {
+                    format!("in {}", format_duration(left))
+                });
+            println!(
+                " • {} {} ({remaining})",
+                hlt("Expires:"),
+                expiration
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+        }
+
+        let mismatches = identity::mismatches(profile_info, &identity);
+        for mismatch in &mismatches {
+            eprintln!(" [!] {mismatch}");
+        }
+        if !mismatches.is_empty() {
+            eprintln!(
+                " Update the profile with {}",
+                hlt(&format!("kee edit {alias}"))
+            );
+        }
+        Ok(mismatches.is_empty())
+    }
+
+    /// The kee profile of an AWS profile name
+    fn profile_info(&self, profile_name: &str) -> Option<ProfileInfo> {
+        self.load_config()
+            .profiles
+            .into_values()
+            .find(|info| info.profile_name == profile_name)
+    }
+
+    /// Ask STS who the profile's credentials belong to, without the AWS CLI.
+    /// `None` when it takes the CLI to find out, see `native_credentials`.
+    #[cfg(feature = "native-sts")]
+    fn native_identity(&self, profile_name: &str) -> Option<Result<CallerIdentity, String>> {
+        let profile_info = self.profile_info(profile_name)?;
+        let credentials = native_credentials(
+            self.aws_manager.sso_cache_dir(),
+            self.aws_manager.cli_cache_dir(),
+            &profile_info,
+        )?;
+        Some(
+            credentials.and_then(|credentials| {
+                sts_client(&profile_info).get_caller_identity(&credentials)
+            }),
+        )
+    }
+
+    #[cfg(not(feature = "native-sts"))]
+    fn native_identity(&self, _: &str) -> Option<Result<CallerIdentity, String>> {
+        None
+    }
+
+    fn caller_identity(&self, profile_name: &str) -> Option<CallerIdentity> {
+        if let Some(identity) = self.native_identity(profile_name) {
+            return identity.ok();
+        }
+
+        let output = Command::new(&self.settings.aws_bin)
+            .args([
+                "sts",
+                "get-caller-identity",
+                "--profile",
+                profile_name,
+                "--output",
+                "json",
+            ])
+            .env(AWS_CLI_AUTO_PROMPT, "off")
+            .env(AWS_PAGER, "")
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+        CallerIdentity::from_json(&String::from_utf8_lossy(&output.stdout)).ok()
+    }
+
+    /// The account's name from the SSO portal, using the cached SSO token
+    fn account_name(&self, profile_info: &ProfileInfo, account_id: &str) -> Option<String> {
+        let path = sso_cache::token_path(self.aws_manager.sso_cache_dir(), profile_info);
+        let token = sso_cache::read_token(&path).ok()?;
+        let region = token.region.as_deref().unwrap_or(&profile_info.sso_region);
+        identity::fetch_account_name(region, &token.access_token, account_id)
+            .ok()
+            .flatten()
+    }
+
+    fn show_history(&self, filter: &HistoryFilter, json: bool) -> io::Result<()> {
+        let entries: Vec<HistoryEntry> = self
+            .history
+            .load()?
+            .into_iter()
+            .filter(|entry| filter.matches(entry))
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+            return Ok(());
+        }
+
+        if entries.is_empty() {
+            eprintln!("\n [!] No matching history entries.");
+            return Ok(());
+        }
+
+        println!();
+        for entry in &entries {
+            let timestamp = entry
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S");
+            let mut details = Vec::new();
+            if let (Some(account_id), Some(role)) = (&entry.account_id, &entry.role) {
+                details.push(format!("{account_id}/{role}"));
+            }
+            if let Some(secs) = entry.duration_secs {
+                details.push(format_duration(Duration::from_secs(secs)));
+            }
+            if let Some(code) = entry.exit_status {
+                details.push(format!("exit {code}"));
+            }
+            if let Some(cwd) = &entry.cwd {
+                details.push(cwd.clone());
+            }
+
+            println!(
+                " {timestamp}  {:<6} {}  {}",
+                entry.action.as_str(),
+                hlt(&entry.profile),
+                details.join("  ")
+            );
+        }
+
+        Ok(())
+    }
+
+    fn show_report(
+        &self,
+        by: GroupBy,
+        tag_key: &str,
+        filter: &HistoryFilter,
+        format: ReportFormat,
+    ) -> io::Result<()> {
+        let entries = self.history.load()?;
+        let sessions = report::sessions_from_history(&entries);
+        let config = self.load_config();
+        let rows = report::build_report(
+            &sessions,
+            &config.profiles,
+            by,
+            tag_key,
+            filter.since,
+            filter.until,
+        );
+
+        if format == ReportFormat::Csv {
+            print!("{}", report::render_csv(&rows, by));
+            return Ok(());
+        }
+
+        if rows.is_empty() {
+            eprintln!("\n [!] No sessions in this period.");
+            return Ok(());
+        }
+
+        let width = rows.iter().map(|row| row.group.len()).max().unwrap_or(0);
+        let total: i64 = rows.iter().map(|row| row.seconds).sum();
+
+        println!();
+        for row in &rows {
+            println!(
+                " {}{}  {:>4} sessions  {}",
+                hlt(&row.group),
+                " ".repeat(width - row.group.len()),
+                row.sessions,
+                format_duration(Duration::from_secs(row.seconds as u64))
+            );
+        }
+        println!(
+            "\n {} {}",
+            hlt("Total:"),
+            format_duration(Duration::from_secs(total as u64))
+        );
+
+        Ok(())
+    }
+
+    fn doctor(&self, fix: bool) -> io::Result<bool> {
+        let mut config = self.load_config();
+        let aws_config = self.aws_manager.load_config()?;
+        let mut findings = doctor::check_profiles(
+            &config.profiles,
+            config.current_profile.as_deref(),
+            aws_config.get_map_ref(),
+        );
+
+        let version = Command::new(&self.settings.aws_bin)
+            .arg("--version")
+            .output()
+            .ok()
+            .map(|output| {
+                format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                )
+            });
+        findings.extend(doctor::check_aws_cli(version.as_deref()));
+
+        // One token per sso-session (or start URL for legacy profiles)
+        let now = chrono::Utc::now();
+        let mut files = vec![
+            self.config_file.clone(),
+            self.aws_manager.config_file().to_path_buf(),
+        ];
+        let mut aliases: Vec<&String> = config.profiles.keys().collect();
+        aliases.sort();
+        let mut seen = HashSet::new();
+        for alias in aliases {
+            let info = &config.profiles[alias];
+            let path = sso_cache::token_path(self.aws_manager.sso_cache_dir(), info);
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+
+            let (subject, login_command) = if info.session_name.is_empty() {
+                (
+                    alias.clone(),
+                    format!("aws sso login --profile {}", info.profile_name),
+                )
+            } else {
+                (
+                    info.session_name.clone(),
+                    format!("aws sso login --sso-session {}", info.session_name),
+                )
+            };
+            let token = sso_cache::read_token(&path).ok();
+            findings.extend(doctor::check_token(
+                &subject,
+                &login_command,
+                token.as_ref(),
+                now,
+            ));
+            files.push(path);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for path in &files {
+                if let Ok(metadata) = fs::metadata(path) {
+                    findings.extend(doctor::check_permissions(
+                        path,
+                        metadata.permissions().mode(),
+                    ));
+                }
+            }
+        }
+
+        if findings.is_empty() {
+            println!("\n [✓] Everything looks good.");
+            return Ok(true);
+        }
+
+        println!();
+        let mut healthy = true;
+        for finding in &findings {
+            let fixed = fix && finding.fix.is_some() && self.apply_fix(finding, &mut config)?;
+            self.print_finding(finding, fixed);
+            if finding.severity == Severity::Error && !fixed {
+                healthy = false;
+            }
+        }
+
+        if fix {
+            self.save_config(&config)?;
+        } else if findings.iter().any(|finding| finding.fix.is_some()) {
+            println!(
+                " Run {} to repair what can be fixed safely.",
+                hlt("kee doctor --fix")
+            );
+        }
+
+        Ok(healthy)
+    }
+
+    fn print_finding(&self, finding: &Finding, fixed: bool) {
+        let marker = match (fixed, finding.severity) {
+            (true, _) => "[✓]",
+            (false, Severity::Warning) => "[!]",
+            (false, Severity::Error) => "[X]",
+        };
+        println!(" {marker} {}: {}", hlt(&finding.subject), finding.message);
+        if fixed {
+            println!("     Fixed.\n");
+        } else {
+            println!("     {}\n", finding.remediation);
+        }
+    }
+
+    fn apply_fix(&self, finding: &Finding, config: &mut KeeConfig) -> io::Result<bool> {
+        match &finding.fix {
+            Some(Fix::RestoreAwsProfile(alias)) => {
+                let Some(info) = config.profiles.get(alias) else {
+                    return Ok(false);
+                };
+                self.aws_manager.write_profile(info)?;
+            }
+            Some(Fix::RestoreSsoSession(alias)) => {
+                let Some(info) = config.profiles.get(alias) else {
+                    return Ok(false);
+                };
+                self.aws_manager.set_section(
+                    &format!("sso-session {}", info.session_name),
+                    &[
+                        ("sso_start_url", info.sso_start_url.as_str()),
+                        ("sso_region", info.sso_region.as_str()),
+                        ("sso_registration_scopes", "sso:account:access"),
+                    ],
+                )?;
+            }
+            Some(Fix::RefreshProfile(alias)) => {
+                let Some(info) = config.profiles.get_mut(alias) else {
+                    return Ok(false);
+                };
+                let Some(fresh) = self.aws_manager.read_profile(&info.profile_name) else {
+                    return Ok(false);
+                };
+                info.update_sso_details(fresh);
+            }
+            Some(Fix::ClearCurrentProfile) => config.current_profile = None,
+            Some(Fix::RestrictPermissions(path)) => {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+                }
+                #[cfg(not(unix))]
+                {
+                    let _ = path;
+                    return Ok(false);
+                }
+            }
+            None => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Run a command for each selected profile, `jobs` at a time, after logging
+    /// in to every SSO session they need. Returns whether all commands succeeded.
+    fn run_each(
+        &self,
+        patterns: &[String],
+        tags: &[(String, String)],
+        all: bool,
+        jobs: usize,
+        command: &[String],
+    ) -> io::Result<bool> {
+        let config = self.load_config();
+        let (aliases, unmatched) = each::select_profiles(&config.profiles, all, tags, patterns);
+        for name in unmatched {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(name));
+        }
+        if aliases.is_empty() {
+            eprintln!("\n [!] No profiles match.");
+            return Ok(false);
+        }
+
+        // Log in up front, once per session: the commands run without a terminal
+        let mut skipped: HashMap<String, String> = HashMap::new();
+        for group in each::login_groups(&config.profiles, &aliases) {
+            let first = &config.profiles[&group[0]];
+            if self.check_credentials(&first.profile_name) || self.login(&group[0], first)? {
+                continue;
+            }
+            for alias in group {
+                skipped.insert(alias, "not logged in".to_string());
+            }
+        }
+
+        // Profiles with a role chain run with the last role's credentials
+        let mut chained: HashMap<String, Credentials> = HashMap::new();
+        for alias in &aliases {
+            let profile_info = &config.profiles[alias];
+            if profile_info.role_chain.is_empty() || skipped.contains_key(alias) {
+                continue;
+            }
+            match self.role_credentials(alias, profile_info)? {
+                Some(credentials) => {
+                    chained.insert(alias.clone(), credentials);
+                }
+                None => {
+                    skipped.insert(alias.clone(), "role chain failed".to_string());
+                }
+            }
+        }
+
+        let width = aliases.iter().map(String::len).max().unwrap_or(0);
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        println!();
+        thread::scope(|scope| {
+            for _ in 0..jobs.min(aliases.len()) {
+                scope.spawn(|| {
+                    while let Some(alias) = aliases.get(next.fetch_add(1, Ordering::SeqCst)) {
+                        let result = match skipped.get(alias) {
+                            Some(reason) => EachResult {
+                                alias: alias.clone(),
+                                outcome: Outcome::Skipped(reason.clone()),
+                                duration: Duration::ZERO,
+                            },
+                            None => self.run_for_profile(
+                                alias,
+                                &config.profiles[alias],
+                                chained.get(alias),
+                                width,
+                                command,
+                            ),
+                        };
+                        results.lock().unwrap().push(result);
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by(|a, b| a.alias.cmp(&b.alias));
+        println!("\n{}", each::render_summary(&results));
+        Ok(results.iter().all(|result| result.outcome.success()))
+    }
+
+    /// Run the `kee each` command for one profile, with the alias in front of
+    /// every line it prints, and record it in the history
+    fn run_for_profile(
+        &self,
+        alias: &str,
+        profile_info: &ProfileInfo,
+        chained: Option<&Credentials>,
+        width: usize,
+        command: &[String],
+    ) -> EachResult {
+        let started = Instant::now();
+        let mut cmd = self.profile_command(&command[0], profile_info);
+        if let Some(credentials) = chained {
+            cmd.envs(docker::credential_env(credentials));
+        }
+        cmd.args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let outcome = match cmd.spawn() {
+            Ok(mut child) => {
+                let stdout = child.stdout.take();
+                let stderr = child.stderr.take();
+                thread::scope(|scope| {
+                    if let Some(stdout) = stdout {
+                        scope.spawn(move || {
+                            each::for_each_line(stdout, |line| {
+                                println!("{}", each::prefix_line(alias, width, line))
+                            })
+                        });
+                    }
+                    if let Some(stderr) = stderr {
+                        scope.spawn(move || {
+                            each::for_each_line(stderr, |line| {
+                                eprintln!("{}", each::prefix_line(alias, width, line))
+                            })
+                        });
+                    }
+                });
+                match child.wait() {
+                    Ok(status) => status.code().map_or(Outcome::Killed, Outcome::Exited),
+                    Err(e) => Outcome::Skipped(e.to_string()),
+                }
+            }
+            Err(e) => Outcome::Skipped(format!("could not start {}: {e}", command[0])),
+        };
+
+        let duration = started.elapsed();
+        if !matches!(outcome, Outcome::Skipped(_)) {
+            let mut entry = HistoryEntry::new(Action::Exec, alias);
+            entry.account_id = Some(profile_info.sso_account_id.clone());
+            entry.role = Some(profile_info.sso_role_name.clone());
+            entry.duration_secs = Some(duration.as_secs());
+            if let Outcome::Exited(code) = outcome {
+                entry.exit_status = Some(code);
+            }
+            self.record(entry);
+        }
+
+        EachResult {
+            alias: alias.to_string(),
+            outcome,
+            duration,
+        }
+    }
+
+    /// Run the `kee-NAME` plugin for `kee NAME ARGS...` with the active (or last
+    /// used) profile's environment and metadata. Returns the plugin's exit code.
+    fn run_plugin(&self, args: &[OsString]) -> io::Result<i32> {
+        let name = args[0].to_string_lossy();
+        let Some(program) = plugin::find_plugin(&name, &env::var_os("PATH").unwrap_or_default())
+        else {
+            // Worded like clap's own errors, since this is one
+            eprintln!(
+                "error: unrecognized subcommand '{name}'\n\n  no {}{name} plugin on PATH either\n\nFor more information, try 'kee help'.",
+                plugin::PLUGIN_PREFIX
+            );
+            return Ok(2);
+        };
+
+        let mut cmd = Command::new(&program);
+        cmd.args(&args[1..]);
+        cmd.env("KEE_BIN", env::current_exe()?);
+        cmd.env("KEE_VERSION", env!("CARGO_PKG_VERSION"));
+        cmd.env("KEE_CONFIG_FILE", &self.config_file);
+
+        let config = self.load_config();
+        let wanted = env::var(KEE_CURRENT_PROFILE)
+            .ok()
+            .or(config.current_profile.clone());
+        let profile = wanted.and_then(|wanted| {
+            config
+                .profiles
+                .iter()
+                .find(|(alias, info)| **alias == wanted || info.profile_name == wanted)
+        });
+        if let Some((alias, profile_info)) = profile {
+            cmd.env(AWS_PROFILE, &profile_info.profile_name);
+            cmd.envs(profile_info.environment());
+            cmd.envs(hooks::profile_env(alias, profile_info));
+        }
+
+        match cmd.status() {
+            Ok(status) => Ok(status.code().unwrap_or(1)),
+            Err(e) => {
+                eprintln!("\n [X] Could not run {}: {e}", program.display());
+                Ok(1)
+            }
+        }
+    }
+
+    fn prompt_prefix(&self, alias: &str, profile_info: &ProfileInfo, shell: PromptShell) -> String {
+        prompt_prefix(
+            &self.settings,
+            self.aws_manager.sso_cache_dir(),
+            alias,
+            profile_info,
+            shell,
+        )
+    }
+
+    fn list_settings(&self) -> io::Result<()> {
+        // An invalid file was already reported and is ignored
+        let file = self.settings_store.load().unwrap_or_default();
+        let width = Key::value_variants()
+            .iter()
+            .map(|key| key.name().len())
+            .max()
+            .unwrap_or(0);
+
+        println!();
+        for key in Key::value_variants() {
+            let source = settings::source(&file, |name| env::var(name).ok(), *key);
+            let origin = match source {
+                settings::Source::Env => format!("{} {}", source.as_str(), key.env_var()),
+                _ => source.as_str().to_string(),
+            };
+            println!(
+                " {}{}  {}  ({origin})",
+                hlt(key.name()),
+                " ".repeat(width - key.name().len()),
+                self.settings.get(*key)
+            );
+        }
+        println!("\n Settings file: {}", self.settings_store.path().display());
+        Ok(())
+    }
+
+    /// Store a setting in the settings file, or remove it with `None`
+    fn set_setting(&self, key: Key, value: Option<&str>) -> io::Result<bool> {
+        let mut file = match self.settings_store.load() {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("\n [X] Fix or remove the settings file first: {e}");
+                return Ok(false);
+            }
+        };
+        if let Err(e) = file.set(key, value) {
+            eprintln!("\n [X] {e}");
+            return Ok(false);
+        }
+        self.settings_store.save(&file)?;
+
+        match value {
+            Some(value) => println!("\n [✓] Set {} to {}.", hlt(key.name()), value),
+            None => println!("\n [✓] {} restored to its default.", hlt(key.name())),
+        }
+        if env::var(key.env_var()).is_ok_and(|value| !value.is_empty()) {
+            eprintln!(
+                " [!] {} is set in your environment and takes precedence.",
+                hlt(key.env_var())
+            );
+        }
+        Ok(true)
+    }
+
+    /// Whether the profile's credentials work. STS is asked directly when the
+    /// SSO cache has what it takes, and its answer is final; the AWS CLI
+    /// handles the rest, such as refreshing an expired SSO token.
+    fn check_credentials(&self, profile_name: &str) -> bool {
+        if let Some(identity) = self.native_identity(profile_name) {
+            return identity.is_ok();
+        }
+
+        match Command::new(&self.settings.aws_bin)
+            .args(["sts", "get-caller-identity", "--profile", profile_name])
+            .env(AWS_CLI_AUTO_PROMPT, "off")
+            .env(AWS_PAGER, "")
+            .output()
+        {
+            Ok(output) => output.status.success(),
+            Err(_) => false,
+        }
+    }
+
+    /// SSO login after finding the credentials expired, recorded in the history
+    fn login(&self, alias: &str, profile_info: &ProfileInfo) -> io::Result<bool> {
+        eprintln!("\n [!] Credentials expired or not available. Attempting SSO login...");
+        let logged_in = self.sso_login(&profile_info.profile_name)?;
+        self.record_profile(
+            Action::Login,
+            alias,
+            Some(profile_info),
+            Some(if logged_in { 0 } else { 1 }),
+        );
+
+        if !logged_in {
+            eprintln!(
+                " [X] Failed to authenticate. Please run {} manually.",
+                hlt("aws sso login")
+            );
+        }
+        Ok(logged_in)
+    }
+
+    fn export_credentials(&self, profile_name: &str) -> Option<Credentials> {
+        let native = self.profile_info(profile_name).and_then(|profile_info| {
+            native_credentials(
+                self.aws_manager.sso_cache_dir(),
+                self.aws_manager.cli_cache_dir(),
+                &profile_info,
+            )
+        });
+        match native {
+            Some(credentials) => credentials.ok(),
+            None => export_credentials(&self.settings.aws_bin, profile_name),
+        }
+    }
+
+    /// Fetches the profile's role credentials without logging in, for a kee
+    /// agent serving them from any thread
+    fn credential_fetcher(
+        &self,
+        alias: &str,
+        profile_info: &ProfileInfo,
+    ) -> impl Fn() -> Result<Credentials, String> + Send + Sync + 'static {
+        let aws_bin = self.settings.aws_bin.clone();
+        let sso_cache_dir = self.aws_manager.sso_cache_dir().to_path_buf();
+        let cli_cache_dir = self.aws_manager.cli_cache_dir().to_path_buf();
+        let alias = alias.to_string();
+        let profile_info = profile_info.clone();
+        move || {
+            let profile_name = &profile_info.profile_name;
+            let expired = || {
+                format!(
+                    "Credentials for '{alias}' expired or not available. Run 'aws sso login --profile {profile_name}' to log in."
+                )
+            };
+            match native_credentials(&sso_cache_dir, &cli_cache_dir, &profile_info) {
+                Some(credentials) => credentials.map_err(|e| format!("{}: {e}", expired())),
+                None => export_credentials(&aws_bin, profile_name).ok_or_else(expired),
+            }
+            .and_then(|credentials| chain_credentials(credentials, &alias, &profile_info))
+        }
+    }
+
+    /// Role credentials for a profile, logging in first if needed. Profiles
+    /// with a role chain get the last role's.
+    fn role_credentials(
+        &self,
+        alias: &str,
+        profile_info: &ProfileInfo,
+    ) -> io::Result<Option<Credentials>> {
+        let credentials = match self.export_credentials(&profile_info.profile_name) {
+            Some(credentials) => credentials,
+            None => {
+                if !self.login(alias, profile_info)? {
+                    return Ok(None);
+                }
+                match self.export_credentials(&profile_info.profile_name) {
+                    Some(credentials) => credentials,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        match chain_credentials(credentials, alias, profile_info) {
+            Ok(credentials) => Ok(Some(credentials)),
+            Err(e) => {
+                eprintln!("\n [X] {e}");
+                Ok(None)
+            }
+        }
+    }
+
+    fn open_console(
+        &self,
+        profile_name: &str,
+        service: Option<&str>,
+        region: Option<&str>,
+        print_only: bool,
+    ) -> io::Result<bool> {
+        let config = self.load_config();
+        let Some(profile_info) = config.profiles.get(profile_name) else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(profile_name));
+            return Ok(false);
+        };
+
+        let Some(credentials) = self.role_credentials(profile_name, profile_info)? else {
+            eprintln!(
+                "\n [X] Could not get credentials for '{}'.",
+                hlt(profile_name)
+            );
+            return Ok(false);
+        };
+
+        let destination = console::destination_url(service, region);
+        let url =
+            match console::signin_url(console::FEDERATION_ENDPOINT, &credentials, &destination) {
+                Ok(url) => url,
+                Err(e) => {
+                    eprintln!("\n [X] {e}");
+                    return Ok(false);
+                }
+            };
+
+        if print_only {
+            println!("{url}");
+            return Ok(true);
+        }
+
+        if !self.open_url(&url, profile_info.browser.as_deref())? {
+            eprintln!("\n [X] Could not open the browser. The sign-in link is:\n\n{url}");
+            return Ok(false);
+        }
+
+        println!("\n [✓] Opened the AWS console for '{}'.", hlt(profile_name));
+        Ok(true)
+    }
+
+    /// Serve the profile's credentials over the container credentials protocol
+    /// until interrupted, refreshing them through the AWS CLI before they expire
+    fn run_agent(&self, alias: &str, port: u16) -> io::Result<bool> {
+        let config = self.load_config();
+        let Some(profile_info) = config.profiles.get(alias) else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(alias));
+            return Ok(false);
+        };
+
+        let Some(credentials) = self.role_credentials(alias, profile_info)? else {
+            eprintln!("\n [X] Could not get credentials for '{}'.", hlt(alias));
+            return Ok(false);
+        };
+
+        let fetch = self.credential_fetcher(alias, profile_info);
+        let agent = agent::Agent::bind(port, agent::generate_token()?, fetch)?;
+        agent.prime(credentials);
+
+        println!(
+            "\n [✓] Serving credentials for '{}' on {}",
+            hlt(alias),
+            hlt(&agent.url()?)
+        );
+        println!();
+        for (name, value) in agent.env()? {
+            println!("export {name}={value}");
+        }
+        println!("\n Press {} to stop.", hlt("Ctrl+C"));
+
+        self.record_profile(Action::Agent, alias, Some(profile_info), None);
+        agent.serve(log_agent_request)?;
+        Ok(true)
+    }
+
+    /// Run docker with the profile's credentials, or a kee agent serving them,
+    /// in the container's environment. Returns docker's exit code.
+    fn run_docker(
+        &self,
+        alias: &str,
+        use_agent: bool,
+        dry_run: bool,
+        args: &[String],
+    ) -> io::Result<i32> {
+        let config = self.load_config();
+        let Some(profile_info) = config.profiles.get(alias) else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(alias));
+            return Ok(1);
+        };
+
+        if docker::mounts_aws_config(args) {
+            eprintln!(
+                "\n [!] The container mounts ~/.aws, which exposes every profile. {} passes the credentials of '{}' without it.",
+                hlt("kee docker"),
+                hlt(alias)
+            );
+        }
+
+        let mut names: Vec<&str> = if use_agent {
+            vec![agent::FULL_URI_VAR, agent::AUTHORIZATION_TOKEN_VAR]
+        } else {
+            vec![
+                "AWS_ACCESS_KEY_ID",
+                "AWS_SECRET_ACCESS_KEY",
+                "AWS_SESSION_TOKEN",
+                "AWS_CREDENTIAL_EXPIRATION",
+            ]
+        };
+        let profile_env = docker::container_env(profile_info);
+        names.extend(profile_env.iter().map(|(name, _)| *name));
+        let docker_args = match docker::docker_args(args, &names, use_agent) {
+            Ok(docker_args) => docker_args,
+            Err(e) => {
+                eprintln!("\n [X] {e}");
+                return Ok(2);
+            }
+        };
+
+        if dry_run {
+            println!("{}", docker::command_line(&docker_args));
+            return Ok(0);
+        }
+
+        if use_agent {
+            let docker_os = (env::consts::OS == "linux")
+                .then(docker_operating_system)
+                .flatten();
+            if let Some(e) = docker::host_network_unsupported(env::consts::OS, docker_os.as_deref())
+            {
+                eprintln!("\n [X] {e}");
+                eprintln!(
+                    " Run without {} to pass the credentials directly.",
+                    hlt("--agent")
+                );
+                return Ok(2);
             }
         }
+
+        let Some(credentials) = self.role_credentials(alias, profile_info)? else {
+            eprintln!("\n [X] Could not get credentials for '{}'.", hlt(alias));
+            return Ok(1);
+        };
+
+        let mut cmd = Command::new(docker::DOCKER);
+        cmd.args(&docker_args);
+        // A forwarded name kee leaves unset must not pass on the host's value
+        for name in &names {
+            cmd.env_remove(name);
+        }
+        cmd.envs(profile_env);
+        if use_agent {
+            let fetch = self.credential_fetcher(alias, profile_info);
+            let agent = agent::Agent::bind(0, agent::generate_token()?, fetch)?;
+            agent.prime(credentials);
+            cmd.envs(agent.env()?);
+            // Serves until kee exits with the container
+            thread::spawn(move || agent.serve(log_agent_request));
+        } else {
+            cmd.envs(docker::credential_env(&credentials));
+        }
+
+        let started = Instant::now();
+        let exit_code = match cmd.status() {
+            Ok(status) => status.code().unwrap_or(1),
+            Err(e) => {
+                eprintln!("\n [X] Could not run {}: {e}", docker::DOCKER);
+                return Ok(1);
+            }
+        };
+
+        let mut entry = HistoryEntry::new(Action::Exec, alias);
+        entry.account_id = Some(profile_info.sso_account_id.clone());
+        entry.role = Some(profile_info.sso_role_name.clone());
+        entry.duration_secs = Some(started.elapsed().as_secs());
+        entry.exit_status = Some(exit_code);
+        self.record(entry);
+        Ok(exit_code)
     }
 
-    fn check_credentials(&self, profile_name: &str) -> bool {
-        match Command::new("aws")
-            .args(["sts", "get-caller-identity", "--profile", profile_name])
+    /// Print the EKS bearer token for a cluster, signed locally with the
+    /// profile's role credentials
+    fn eks_token(&self, alias: &str, cluster: &str, region: Option<&str>) -> io::Result<bool> {
+        let config = self.load_config();
+        let Some(profile_info) = config.profiles.get(alias) else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(alias));
+            return Ok(false);
+        };
+        let region = region
+            .or(profile_info.region.as_deref())
+            .unwrap_or(&profile_info.sso_region);
+
+        let Some(credentials) = self.role_credentials(alias, profile_info)? else {
+            eprintln!("\n [X] Could not get credentials for '{}'.", hlt(alias));
+            return Ok(false);
+        };
+
+        let now = chrono::Utc::now();
+        let token = eks::token(&credentials, cluster, region, now);
+        println!("{}", eks::exec_credential(&token, now));
+        Ok(true)
+    }
+
+    /// Add a kubeconfig context for a cluster whose user runs `kee eks token`
+    fn eks_kubeconfig(
+        &self,
+        alias: &str,
+        cluster_name: &str,
+        region: Option<&str>,
+        name: Option<&str>,
+    ) -> io::Result<bool> {
+        let config = self.load_config();
+        let Some(profile_info) = config.profiles.get(alias) else {
+            eprintln!("\n [!] Profile '{}' not found.", hlt(alias));
+            return Ok(false);
+        };
+        let region = region
+            .or(profile_info.region.as_deref())
+            .unwrap_or(&profile_info.sso_region);
+
+        // Log in first if needed, describe-cluster would only fail
+        if self.role_credentials(alias, profile_info)?.is_none() {
+            eprintln!("\n [X] Could not get credentials for '{}'.", hlt(alias));
+            return Ok(false);
+        }
+
+        let output = Command::new(&self.settings.aws_bin)
+            .args(["eks", "describe-cluster", "--name", cluster_name])
+            .args(["--region", region, "--profile", &profile_info.profile_name])
+            .args(["--output", "json"])
             .env(AWS_CLI_AUTO_PROMPT, "off")
             .env(AWS_PAGER, "")
-            .output()
+            .output()?;
+        if !output.status.success() {
+            eprintln!(
+                "\n [X] Could not find cluster '{}' in {region}: {}",
+                hlt(cluster_name),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Ok(false);
+        }
+        let cluster = match eks::Cluster::from_json(&String::from_utf8_lossy(&output.stdout))
+            .and_then(|cluster| cluster.certificate().map(|pem| (cluster, pem)))
         {
-            Ok(output) => output.status.success(),
-            Err(_) => false,
+            Ok(cluster) => cluster,
+            Err(e) => {
+                eprintln!("\n [X] {e}");
+                return Ok(false);
+            }
+        };
+        let (cluster, certificate) = cluster;
+
+        // kubectl embeds the certificate from a file, removed when dropped
+        let mut certificate_file = tempfile::Builder::new()
+            .prefix("kee-eks-")
+            .suffix(".pem")
+            .tempfile()?;
+        certificate_file.write_all(&certificate)?;
+
+        let name = name
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{alias}/{cluster_name}"));
+        let kee_bin = env::current_exe()?.to_string_lossy().to_string();
+        let mut result = Ok(true);
+        for args in eks::kubeconfig_commands(
+            &name,
+            &cluster,
+            certificate_file.path(),
+            &kee_bin,
+            alias,
+            region,
+        ) {
+            match Command::new(eks::KUBECTL).args(&args).output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => {
+                    eprintln!(
+                        "\n [X] kubectl {} failed: {}",
+                        args[1],
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                    result = Ok(false);
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("\n [X] Could not run {}: {e}", eks::KUBECTL);
+                    result = Ok(false);
+                    break;
+                }
+            }
+        }
+        let _ = certificate_file.close();
+
+        if let Ok(true) = result {
+            println!(
+                "\n [✓] Added context '{}' for {} and switched to it.",
+                hlt(&name),
+                hlt(&cluster.arn)
+            );
+            println!(" kubectl gets its tokens from {}.", hlt("kee eks token"));
         }
+        result
+    }
+
+    /// Open a URL with the profile's browser command, or the system default.
+    /// The URL is passed through the environment so it never needs shell quoting.
+    fn open_url(&self, url: &str, browser: Option<&str>) -> io::Result<bool> {
+        let mut cmd = match browser {
+            Some(template) => {
+                let placeholder = if cfg!(windows) {
+                    "\"%KEE_CONSOLE_URL%\""
+                } else {
+                    "\"$KEE_CONSOLE_URL\""
+                };
+                let command = if template.contains("{url}") {
+                    template.replace("{url}", placeholder)
+                } else {
+                    format!("{template} {placeholder}")
+                };
+
+                let (shell, flag) = shell_invocation();
+                let mut cmd = Command::new(shell);
+                cmd.args([flag, &command]);
+                cmd
+            }
+            None if cfg!(target_os = "macos") => {
+                let mut cmd = Command::new("open");
+                cmd.arg(url);
+                cmd
+            }
+            None if cfg!(windows) => {
+                let mut cmd = Command::new("rundll32");
+                cmd.args(["url.dll,FileProtocolHandler", url]);
+                cmd
+            }
+            None => {
+                let mut cmd = Command::new("xdg-open");
+                cmd.arg(url);
+                cmd
+            }
+        };
+
+        cmd.env(KEE_CONSOLE_URL, url);
+        Ok(cmd.status().is_ok_and(|status| status.success()))
     }
 
     fn sso_login(&self, profile_name: &str) -> io::Result<bool> {
-        let status = Command::new("aws")
+        // The login instructions are messages, not data: commands like
+        // `kee eks token` print JSON that must not mix with them
+        let status = Command::new(&self.settings.aws_bin)
             .args(["sso", "login", "--profile", profile_name])
+            .stdout(std::process::Stdio::from(io::stderr()))
             .status()?;
 
         Ok(status.success())
     }
 
-    fn start_subshell(&self, profile_name: &str) -> io::Result<()> {
-        // Get current shell
-        let shell = if cfg!(windows) {
-            env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    /// Run the global and profile hooks for an event. Stops at the first failing
+    /// `on_enter` hook; `on_exit` hooks all run and failures are only reported.
+    fn run_hooks(
+        &self,
+        event: HookEvent,
+        alias: &str,
+        profile_info: &ProfileInfo,
+        extra_env: &[(&str, String)],
+    ) -> bool {
+        let mut success = true;
+
+        for hook in hooks::hooks_for(event, &self.hooks_dir, profile_info) {
+            let (mut cmd, description) = match &hook {
+                Hook::Global(path) => (
+                    self.profile_command(&path.to_string_lossy(), profile_info),
+                    path.display().to_string(),
+                ),
+                Hook::Profile(command) => {
+                    let (shell, flag) = shell_invocation();
+                    let mut cmd = self.profile_command(shell, profile_info);
+                    cmd.args([flag, command]);
+                    (cmd, format!("{} of '{alias}'", event.name()))
+                }
+            };
+            cmd.envs(hooks::hook_env(alias, profile_info, event));
+            cmd.envs(extra_env.iter().map(|(name, value)| (*name, value)));
+
+            let status = cmd.status();
+            if status.as_ref().is_ok_and(|status| status.success()) {
+                continue;
+            }
+
+            match status {
+                Ok(status) => eprintln!("\n [X] Hook {} failed ({status}).", hlt(&description)),
+                Err(e) => eprintln!("\n [X] Could not run hook {}: {e}", hlt(&description)),
+            }
+            success = false;
+            if event == HookEvent::Enter {
+                break;
+            }
+        }
+
+        success
+    }
+
+    fn print_hook(&self, shell: HookShell) -> io::Result<()> {
+        let kee = env::current_exe()?;
+        print!("{}", project::hook_script(shell, &kee.to_string_lossy()));
+        Ok(())
+    }
+
+    fn trust(&self, path: Option<&Path>, revoke: bool) -> io::Result<bool> {
+        let file = match path {
+            Some(path) if path.is_dir() => project::PROJECT_FILES
+                .iter()
+                .map(|name| path.join(name))
+                .find(|file| file.is_file()),
+            Some(path) => Some(path.to_path_buf()),
+            None => project::find_project_file(&env::current_dir()?),
+        };
+        let Some(file) = file.and_then(|file| file.canonicalize().ok()) else {
+            eprintln!("\n [!] No .kee or .kee.toml file found.");
+            return Ok(false);
+        };
+        let display = file.display().to_string();
+
+        if revoke {
+            if self.trust_store.revoke(&file)? {
+                println!("\n [✓] {} is no longer trusted.", hlt(&display));
+            } else {
+                eprintln!("\n [!] {} was not trusted.", hlt(&display));
+            }
+            return Ok(true);
+        }
+
+        let content = fs::read_to_string(&file)?;
+        let alias = match project::parse_project_file(&file, &content) {
+            Ok(alias) => alias,
+            Err(e) => {
+                eprintln!("\n [X] {e}");
+                return Ok(false);
+            }
+        };
+
+        self.trust_store.trust(&file, &content)?;
+        println!(
+            "\n [✓] Trusted {} (profile {}).",
+            hlt(&display),
+            hlt(&alias)
+        );
+        if !self.load_config().profiles.contains_key(&alias) {
+            eprintln!(
+                " [!] Profile '{}' does not exist yet. Add it with {}",
+                hlt(&alias),
+                hlt(&format!("kee add {alias}"))
+            );
+        }
+        Ok(true)
+    }
+
+    /// Print the shell code that switches to the current directory's profile.
+    /// Only prints when the project file, its contents or its trust changed.
+    fn export_env(&self, shell: HookShell) -> io::Result<()> {
+        // Inside a kee use sub-shell the session's profile wins
+        if env::var(KEE_ACTIVE_PROFILE).is_ok() {
+            return Ok(());
+        }
+
+        let previous_state = env::var(project::DIR_STATE_VAR).ok();
+        let previous_vars: Vec<String> = env::var(project::DIR_VARS_VAR)
+            .unwrap_or_default()
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        let found = project::find_project_file(&env::current_dir()?)
+            .and_then(|file| Some((fs::read_to_string(&file).ok()?, file)));
+        let Some((content, file)) = found else {
+            if previous_state.is_some() {
+                if !previous_vars.is_empty() {
+                    eprintln!("kee: unloading the directory profile");
+                }
+                print!(
+                    "{}",
+                    project::export_script(shell, &previous_vars, &[], None, |name| {
+                        env::var(name).ok()
+                    })
+                );
+            }
+            return Ok(());
+        };
+
+        let trusted = self.trust_store.is_trusted(&file, &content);
+        let state = format!(
+            "{}#{}#{}",
+            file.display(),
+            project::content_hash(&content),
+            if trusted { "trusted" } else { "untrusted" }
+        );
+        if previous_state.as_deref() == Some(state.as_str()) {
+            return Ok(());
+        }
+
+        let mut vars = Vec::new();
+        if !trusted {
+            eprintln!(
+                "kee: {} is not trusted. Run `kee trust` to allow it.",
+                file.display()
+            );
         } else {
-            env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
+            match project::parse_project_file(&file, &content) {
+                Err(e) => eprintln!("kee: {e}"),
+                Ok(alias) => match self.load_config().profiles.get(&alias) {
+                    None => eprintln!("kee: profile '{alias}' from {} not found", file.display()),
+                    Some(profile_info) => {
+                        eprintln!("kee: using profile {alias}");
+                        let profile_name = &profile_info.profile_name;
+                        vars.push((AWS_PROFILE.to_string(), profile_name.clone()));
+                        vars.push((KEE_CURRENT_PROFILE.to_string(), profile_name.clone()));
+                        vars.extend(
+                            profile_info
+                                .environment()
+                                .into_iter()
+                                .map(|(name, value)| (name.to_string(), value.to_string())),
+                        );
+                    }
+                },
+            }
+        }
+
+        print!(
+            "{}",
+            project::export_script(shell, &previous_vars, &vars, Some(&state), |name| {
+                env::var(name).ok()
+            })
+        );
+        Ok(())
+    }
+
+    /// A command that runs with the profile's credentials and environment
+    fn profile_command(&self, program: &str, profile_info: &ProfileInfo) -> Command {
+        let profile_name = &profile_info.profile_name;
+        let mut cmd = Command::new(program);
+        cmd.env(AWS_PROFILE, profile_name);
+        cmd.env(KEE_CURRENT_PROFILE, profile_name);
+        cmd.env(KEE_ACTIVE_PROFILE, "1");
+        cmd.envs(profile_info.environment());
+        cmd
+    }
+
+    fn start_subshell(
+        &self,
+        alias: &str,
+        profile_info: &ProfileInfo,
+        timeout: Option<Duration>,
+        chained: Option<&Credentials>,
+    ) -> io::Result<Option<i32>> {
+        let profile_name = profile_info.profile_name.as_str();
+        // The shell setting, or the current shell
+        let shell = match &self.settings.shell {
+            Some(shell) => shell.clone(),
+            None if cfg!(windows) => env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string()),
+            None => env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string()),
         };
 
         // Show banner
         println!("\n Profile: {}", hlt(profile_name));
         println!(" {} is starting a sub-shell...", hlt("Kee"));
         println!(" Type {} to return to your main shell.", hlt("exit"));
+        if let Some(timeout) = timeout {
+            println!(
+                " The session will end automatically in {}.",
+                hlt(&format_duration(timeout))
+            );
+        }
+        if let (Some(role), Some(credentials)) = (profile_info.role_chain.last(), chained) {
+            let until = credentials.expiration.map_or(String::new(), |expiration| {
+                format!(
+                    " until {}",
+                    expiration.with_timezone(&chrono::Local).format("%H:%M")
+                )
+            });
+            println!(" Assumed {}{until}.", hlt(&role.role_arn));
+        }
 
         // Start subshell with environment
-        let mut cmd = Command::new(&shell);
-        cmd.env(AWS_PROFILE, profile_name);
-        cmd.env(KEE_CURRENT_PROFILE, profile_name);
-        cmd.env(KEE_ACTIVE_PROFILE, "1");
+        let mut cmd = self.profile_command(&shell, profile_info);
+        if let Some(credentials) = chained {
+            cmd.envs(docker::credential_env(credentials));
+        }
 
-        // Update PS1 for Unix-like systems
-        if !cfg!(windows) {
-            if let Ok(ps1) = env::var("PS1") {
-                cmd.env("PS1", format!("aws:{profile_name} {ps1}"));
-            } else {
-                cmd.env("PS1", format!("aws:{profile_name} $ "));
+        // Shell-specific setup so the prompt prefix survives the user's rc files.
+        // The directory is created private to the user and removed when dropped.
+        let bootstrap_dir = tempfile::Builder::new().prefix("kee-").tempdir()?;
+        let kind = ShellKind::detect(&shell);
+        match shell::bootstrap(
+            kind,
+            &self.prompt_prefix(alias, profile_info, PromptShell::for_kind(kind)),
+            bootstrap_dir.path(),
+            env::var("PS1").ok().as_deref(),
+        ) {
+            Ok(bootstrap) => {
+                cmd.args(bootstrap.args);
+                cmd.envs(bootstrap.env);
+                // A fixed prefix would show the time left at the start forever
+                if self.settings.prompt.contains("{ttl}") {
+                    if let Ok(exe) = env::current_exe() {
+                        cmd.env(shell::PROMPT_EXE_VAR, exe);
+                    }
+                }
             }
+            Err(e) => eprintln!(" [!] Could not set up the shell prompt: {e}"),
         }
 
-        let _ = cmd.status();
+        let exit_status = match cmd.spawn() {
+            Ok(mut child) => {
+                // Registered so `kee logout` can end the sub-shell
+                let pid = child.id();
+                if let Err(e) = self
+                    .live_sessions
+                    .register(&LiveSession::new(pid, profile_info))
+                {
+                    eprintln!(" [!] Could not register the session: {e}");
+                }
+                let exit_status = match timeout {
+                    Some(timeout) => self.wait_with_timeout(child, profile_name, timeout),
+                    None => child.wait().map(|status| status.code()),
+                };
+                self.live_sessions.unregister(pid);
+                exit_status?
+            }
+            Err(_) => None,
+        };
 
+        let _ = bootstrap_dir.close();
         println!("\n {} — Session ended.", hlt(profile_name));
-        Ok(())
+        Ok(exit_status)
+    }
+
+    fn wait_with_timeout(
+        &self,
+        mut child: std::process::Child,
+        profile_name: &str,
+        timeout: Duration,
+    ) -> io::Result<Option<i32>> {
+        let started = Instant::now();
+        // Only warn about thresholds that are shorter than the whole session
+        let mut warnings: Vec<Duration> = SESSION_WARNINGS
+            .iter()
+            .copied()
+            .filter(|warning| *warning < timeout)
+            .collect();
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status.code());
+            }
+
+            let elapsed = started.elapsed();
+            if elapsed >= timeout {
+                eprintln!(
+                    "\n\n [!] The session for {} has reached its time limit.",
+                    hlt(profile_name)
+                );
+                // A hangup lets the shell save its history and run its traps
+                hang_up(child.id());
+                let hung_up = Instant::now();
+                while child.try_wait()?.is_none() {
+                    if hung_up.elapsed() >= HANGUP_GRACE {
+                        let _ = child.kill();
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                let _ = child.wait();
+                return Ok(None);
+            }
+
+            let remaining = timeout - elapsed;
+            if let Some(index) = warnings.iter().position(|warning| remaining <= *warning) {
+                let warning = warnings.remove(index);
+                eprintln!(
+                    "\n\n [!] The session for {} ends in {}.",
+                    hlt(profile_name),
+                    hlt(&format_duration(warning))
+                );
+            }
+
+            thread::sleep(Duration::from_millis(250).min(remaining));
+        }
+    }
+}
+
+/// The `OperatingSystem` of the docker daemon, e.g. `Docker Desktop`
+fn docker_operating_system() -> Option<String> {
+    let output = Command::new(docker::DOCKER)
+        .args(["info", "--format", "{{.OperatingSystem}}"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Ask a sub-shell to exit, as closing its terminal would. Whether the signal
+/// was delivered.
+fn hang_up(pid: u32) -> bool {
+    let pid = pid.to_string();
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/PID", &pid]);
+        cmd
+    } else {
+        // Interactive shells ignore SIGTERM but exit on a hangup
+        let mut cmd = Command::new("kill");
+        cmd.args(["-HUP", &pid]);
+        cmd
+    };
+    cmd.stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The prompt prefix of a profile, from the prompt settings and local state only
+fn prompt_prefix(
+    settings: &Settings,
+    sso_cache_dir: &Path,
+    alias: &str,
+    profile_info: &ProfileInfo,
+    shell: PromptShell,
+) -> String {
+    let token_path = sso_cache::token_path(sso_cache_dir, profile_info);
+    let ttl = sso_cache::read_token(&token_path)
+        .map(|token| prompt::format_ttl(token.expires_at, chrono::Utc::now()))
+        .unwrap_or_default();
+    let text = prompt::render(&settings.prompt, alias, profile_info, &ttl);
+
+    match prompt::tag_color(profile_info, &settings.prompt_colors) {
+        // Prompts are printed through `$(...)`, never to a terminal
+        Some(color) if ui::use_colors(settings.color, |name| env::var(name).ok(), true) => {
+            prompt::colorize(&text, color, shell)
+        }
+        _ => text,
+    }
+}
+
+/// Print the prefix for the profile of the current kee sub-shell or .kee
+/// directory, and nothing elsewhere. Runs before every prompt, so it must
+/// stay fast and quiet: no AWS calls, no `~/.kee` to create and no warnings
+/// about the settings, which the other commands report.
+fn print_prompt(shell: PromptShell, format: Option<String>, color: Option<ColorChoice>) {
+    let Ok(current) = env::var(KEE_CURRENT_PROFILE) else {
+        return;
+    };
+    let Some(home_dir) = dirs::home_dir() else {
+        return;
+    };
+    let kee_dir = home_dir.join(".kee");
+    let config = read_config(&kee_dir.join("config.json"));
+    let Some((alias, profile_info)) = config
+        .profiles
+        .iter()
+        .find(|(alias, info)| info.profile_name == current || **alias == current)
+    else {
+        return;
+    };
+
+    let file = SettingsStore::new(kee_dir.join("settings.toml"))
+        .load()
+        .unwrap_or_default();
+    let (mut settings, _) = Settings::resolve(&file, |name| env::var(name).ok());
+    if let Some(format) = format {
+        settings.prompt = format;
+    }
+    if let Some(color) = color {
+        settings.color = color;
+    }
+    let sso_cache_dir = home_dir.join(".aws").join("sso").join("cache");
+    print!(
+        "{}",
+        prompt_prefix(&settings, &sso_cache_dir, alias, profile_info, shell)
+    );
+}
+
+fn time_filter(since: Option<String>, until: Option<String>) -> HistoryFilter {
+    let now = chrono::Utc::now();
+    let parse = |value: Option<String>| match value {
+        Some(value) => history::parse_time(&value, now).map(Some),
+        None => Ok(None),
+    };
+
+    match (parse(since), parse(until)) {
+        (Ok(since), Ok(until)) => HistoryFilter {
+            since,
+            until,
+            ..Default::default()
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("\n [X] {e}");
+            std::process::exit(2);
+        }
     }
 }
 
 fn main() -> io::Result<()> {
-    let cli = match Cli::try_parse() {
+    // Answer completion requests from the scripts printed by kee completions
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE)
+        .complete();
+
+    // Until the settings are read, for parse errors
+    ui::set_colors(ui::use_colors(
+        ColorChoice::Auto,
+        |name| env::var(name).ok(),
+        ui::is_terminal(),
+    ));
+
+    let cli = match cli_command()
+        .try_get_matches()
+        .and_then(|matches| Cli::from_arg_matches(&matches))
+    {
         Ok(cli) => cli,
         Err(err) => {
             // Check if it's a missing argument error and customize the message
@@ -466,23 +3386,200 @@ fn main() -> io::Result<()> {
         }
     };
 
-    let kee = KeeManager::new()?;
+    if let Commands::Prompt { shell, format } = cli.command {
+        if let Some(format) = &format {
+            if let Err(e) = prompt::validate_template(format) {
+                eprintln!("\n [X] {e}");
+                std::process::exit(2);
+            }
+        }
+        print_prompt(shell, format, cli.color);
+        return Ok(());
+    }
+
+    let mut kee = KeeManager::new()?;
+    if let Some(color) = cli.color {
+        kee.settings.color = color;
+    }
+    ui::set_colors(ui::use_colors(
+        kee.settings.color,
+        |name| env::var(name).ok(),
+        ui::is_terminal(),
+    ));
 
     match cli.command {
-        Commands::Add { profile_name } => {
-            kee.add_profile(&profile_name)?;
+        Commands::Add {
+            profile_name,
+            max_session,
+            tags,
+        } => {
+            kee.add_profile(&profile_name, max_session, tags.into_iter().collect())?;
         }
-        Commands::Use { profile_name } => {
-            kee.use_profile(&profile_name)?;
+        Commands::Use {
+            profile_name,
+            duration,
+            shell,
+        } => {
+            if shell.is_some() {
+                kee.settings.shell = shell;
+            }
+            kee.use_profile(&profile_name, duration.as_deref())?;
         }
-        Commands::Ls { names } => {
-            kee.list_profiles(names);
+        Commands::Ls { names, json } => {
+            kee.list_profiles(names, json)?;
         }
         Commands::Current => {
             kee.current_profile();
         }
-        Commands::Rm { profile_name } => {
-            kee.remove_profile(&profile_name)?;
+        Commands::Whoami { profile_name } => {
+            kee.whoami(profile_name.as_deref())?;
+        }
+        Commands::Rm { profile_names, yes } => {
+            kee.remove_profiles(&profile_names, yes)?;
+        }
+        Commands::Rename { old_name, new_name } => {
+            kee.rename_profile(&old_name, &new_name)?;
+        }
+        Commands::Edit {
+            profile_name,
+            changes,
+        } => {
+            kee.edit_profile(&profile_name, &changes)?;
+        }
+        Commands::Console {
+            profile_name,
+            service,
+            region,
+            print,
+        } => {
+            kee.open_console(&profile_name, service.as_deref(), region.as_deref(), print)?;
+        }
+        Commands::Docker {
+            profile_name,
+            agent,
+            dry_run,
+            args,
+        } => {
+            std::process::exit(kee.run_docker(&profile_name, agent, dry_run, &args)?);
+        }
+        Commands::Agent { profile_name, port } => {
+            if !kee.run_agent(&profile_name, port.unwrap_or(0))? {
+                std::process::exit(1);
+            }
+        }
+        Commands::History {
+            profile,
+            action,
+            since,
+            until,
+            json,
+        } => {
+            let filter = HistoryFilter {
+                profile,
+                action,
+                ..time_filter(since, until)
+            };
+            kee.show_history(&filter, json)?;
+        }
+        Commands::Hook { shell } => {
+            kee.print_hook(shell)?;
+        }
+        Commands::Trust { path, revoke } => {
+            kee.trust(path.as_deref(), revoke)?;
+        }
+        Commands::Each {
+            profile_names,
+            tags,
+            all,
+            jobs,
+            command,
+        } => {
+            if !kee.run_each(&profile_names, &tags, all, jobs, &command)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Logout { session, all: _ } => {
+            kee.logout(session.as_deref())?;
+        }
+        Commands::Eks { command } => {
+            let success = match command {
+                EksCommand::Token {
+                    profile_name,
+                    cluster,
+                    region,
+                } => kee.eks_token(&profile_name, &cluster, region.as_deref())?,
+                EksCommand::Kubeconfig {
+                    profile_name,
+                    cluster,
+                    region,
+                    name,
+                } => {
+                    kee.eks_kubeconfig(&profile_name, &cluster, region.as_deref(), name.as_deref())?
+                }
+            };
+            if !success {
+                std::process::exit(1);
+            }
+        }
+        Commands::Session { command } => match command {
+            SessionCommand::Ls => kee.list_sessions()?,
+            SessionCommand::Add {
+                name,
+                start_url,
+                region,
+                scopes,
+            } => {
+                kee.add_session(&name, &start_url, &region, &scopes)?;
+            }
+            SessionCommand::Rm { name, yes } => {
+                kee.remove_session(&name, yes)?;
+            }
+            SessionCommand::Login { name } => {
+                kee.session_login(&name)?;
+            }
+            SessionCommand::Logout { name } => {
+                kee.logout(Some(&name))?;
+            }
+            SessionCommand::SetUrl { name, url, region } => {
+                kee.set_session_url(&name, &url, region.as_deref())?;
+            }
+        },
+        // Handled before the manager is set up
+        Commands::Prompt { .. } => {}
+        Commands::Config { command } => match command {
+            ConfigCommand::List => kee.list_settings()?,
+            ConfigCommand::Get { key } => println!("{}", kee.settings.get(key)),
+            ConfigCommand::Set { key, value } => {
+                if !kee.set_setting(key, Some(&value))? {
+                    std::process::exit(1);
+                }
+            }
+            ConfigCommand::Unset { key } => {
+                kee.set_setting(key, None)?;
+            }
+        },
+        Commands::Completions { shell } => {
+            print_completions(shell)?;
+        }
+        Commands::Export { shell } => {
+            kee.export_env(shell)?;
+        }
+        Commands::Plugin(args) => {
+            std::process::exit(kee.run_plugin(&args)?);
+        }
+        Commands::Doctor { fix } => {
+            if !kee.doctor(fix)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Report {
+            since,
+            until,
+            by,
+            tag_key,
+            format,
+        } => {
+            kee.show_report(by, &tag_key, &time_filter(since, until), format)?;
         }
     }
 
@@ -0,0 +1,61 @@
+#![allow(dead_code)]
+
+/// Shell-style wildcard match supporting `*` and `?`
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+pub fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?'])
+}
+
+/// Resolve names and patterns against the known names.
+/// Returns the matches in order (without duplicates) and the inputs that matched nothing.
+pub fn resolve<'a>(inputs: &'a [String], names: &[String]) -> (Vec<String>, Vec<&'a str>) {
+    let mut matched: Vec<String> = Vec::new();
+    let mut unmatched = Vec::new();
+
+    for input in inputs {
+        let hits: Vec<&String> = if is_pattern(input) {
+            names
+                .iter()
+                .filter(|name| glob_match(input, name))
+                .collect()
+        } else {
+            names.iter().filter(|name| *name == input).collect()
+        };
+
+        if hits.is_empty() {
+            unmatched.push(input.as_str());
+        }
+        for hit in hits {
+            if !matched.contains(hit) {
+                matched.push(hit.clone());
+            }
+        }
+    }
+
+    (matched, unmatched)
+}
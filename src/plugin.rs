@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `kee foo` runs the first `kee-foo` executable on `PATH`
+pub const PLUGIN_PREFIX: &str = "kee-";
+
+/// Extensions Windows runs without being named
+const WINDOWS_EXTENSIONS: [&str; 3] = ["exe", "cmd", "bat"];
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// The plugin name of an executable file name, e.g. `foo` for `kee-foo` (or `kee-foo.exe`)
+fn plugin_name(file_name: &str) -> Option<&str> {
+    let name = file_name.strip_prefix(PLUGIN_PREFIX)?;
+    let name = if cfg!(windows) {
+        let (stem, extension) = name.rsplit_once('.')?;
+        if !WINDOWS_EXTENSIONS.contains(&extension.to_lowercase().as_str()) {
+            return None;
+        }
+        stem
+    } else {
+        name
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// The executable of plugin `name` in the `PATH`-style list of directories
+pub fn find_plugin(name: &str, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path).find_map(|dir| {
+        let base = dir.join(format!("{PLUGIN_PREFIX}{name}"));
+        let candidates = if cfg!(windows) {
+            WINDOWS_EXTENSIONS
+                .iter()
+                .map(|extension| base.with_extension(extension))
+                .collect()
+        } else {
+            vec![base]
+        };
+        candidates
+            .into_iter()
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+/// All plugins on `PATH` by name; like the shell, the first directory wins
+pub fn find_plugins(path: &OsStr) -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for dir in env::split_paths(path) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(name) = plugin_name(&file_name) {
+                if !plugins.contains_key(name) && is_executable(&entry.path()) {
+                    plugins.insert(name.to_string(), entry.path());
+                }
+            }
+        }
+    }
+    plugins
+}
+
+/// The plugin section of `kee help`
+pub fn render_plugins(plugins: &BTreeMap<String, PathBuf>) -> String {
+    let width = plugins.keys().map(String::len).max().unwrap_or(0);
+    let lines: Vec<String> = plugins
+        .iter()
+        .map(|(name, path)| format!("  {name:<width$}  {}", path.display()))
+        .collect();
+    format!("Plugins:\n{}", lines.join("\n"))
+}
@@ -0,0 +1,240 @@
+#![allow(dead_code)]
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Files naming a project's profile, in order of preference
+pub const PROJECT_FILES: [&str; 2] = [".kee.toml", ".kee"];
+
+/// Shell variable remembering the last evaluated project file, so the hook
+/// only prints something when the directory state changes
+pub const DIR_STATE_VAR: &str = "KEE_DIR_STATE";
+/// Shell variable listing the variables exported for the project, to unset them on leave
+pub const DIR_VARS_VAR: &str = "KEE_DIR_VARS";
+/// Prefix of the shell variables keeping the values the project replaced,
+/// e.g. `KEE_DIR_SAVED_AWS_PROFILE`, to restore them on leave
+pub const DIR_SAVED_PREFIX: &str = "KEE_DIR_SAVED_";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Deserialize)]
+struct ProjectToml {
+    profile: String,
+}
+
+/// The nearest `.kee.toml` or `.kee` file in `start` or one of its parents.
+/// `~/.kee` is kee's own config directory, so only files count.
+pub fn find_project_file(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find_map(|dir| {
+        PROJECT_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The profile named by a project file: a TOML `profile = "..."` for `.kee.toml`,
+/// or the first line that isn't blank or a `#` comment for `.kee`
+pub fn parse_project_file(path: &Path, content: &str) -> Result<String, String> {
+    let profile = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str::<ProjectToml>(content)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e.message()))?
+            .profile
+    } else {
+        content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let profile = profile.trim().to_string();
+    if profile.is_empty() {
+        return Err(format!("{} does not name a profile", path.display()));
+    }
+    Ok(profile)
+}
+
+pub fn content_hash(content: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Project files allowed to select a profile, with the hash of the trusted
+/// contents: editing a file revokes its trust until `kee trust` is run again
+pub struct TrustStore {
+    path: PathBuf,
+}
+
+impl TrustStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn load(&self) -> io::Result<BTreeMap<String, String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, trusted: &BTreeMap<String, String>) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(trusted)?;
+        fs::write(&self.path, content)
+    }
+
+    pub fn is_trusted(&self, file: &Path, content: &str) -> bool {
+        self.load().is_ok_and(|trusted| {
+            trusted.get(&file.to_string_lossy().to_string()) == Some(&content_hash(content))
+        })
+    }
+
+    pub fn trust(&self, file: &Path, content: &str) -> io::Result<()> {
+        let mut trusted = self.load()?;
+        trusted.insert(file.to_string_lossy().to_string(), content_hash(content));
+        self.save(&trusted)
+    }
+
+    /// Returns whether the file was trusted
+    pub fn revoke(&self, file: &Path) -> io::Result<bool> {
+        let mut trusted = self.load()?;
+        let removed = trusted
+            .remove(&file.to_string_lossy().to_string())
+            .is_some();
+        if removed {
+            self.save(&trusted)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Shell code installing the hook that runs `kee export` before each prompt
+pub fn hook_script(shell: HookShell, kee: &str) -> String {
+    let kee = quote(shell, kee);
+    match shell {
+        HookShell::Bash => format!(
+            r#"_kee_hook() {{
+  local previous_exit_status=$?
+  eval "$({kee} export bash)"
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_kee_hook;"* ]]; then
+  PROMPT_COMMAND="_kee_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#
+        ),
+        HookShell::Zsh => format!(
+            r#"_kee_hook() {{
+  eval "$({kee} export zsh)"
+}}
+typeset -ag precmd_functions
+if (( ! ${{precmd_functions[(I)_kee_hook]}} )); then
+  precmd_functions=(_kee_hook $precmd_functions)
+fi
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_kee_hook]}} )); then
+  chpwd_functions=(_kee_hook $chpwd_functions)
+fi
+"#
+        ),
+        HookShell::Fish => format!(
+            r#"function _kee_hook --on-event fish_prompt --on-variable PWD
+  {kee} export fish | source
+end
+"#
+        ),
+    }
+}
+
+/// Shell code restoring `previous` variables, then exporting `vars` and the new
+/// state. `current` looks up the shell's variables: the values `vars` replace
+/// are saved, and restored when a later call leaves them out.
+pub fn export_script(
+    shell: HookShell,
+    previous: &[String],
+    vars: &[(String, String)],
+    state: Option<&str>,
+    current: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut lines = Vec::new();
+    for name in previous {
+        if vars.iter().any(|(new, _)| new == name) {
+            continue;
+        }
+        let saved = saved_var(name);
+        match current(&saved) {
+            Some(value) => {
+                lines.push(export(shell, name, &value));
+                lines.push(unset(shell, &saved));
+            }
+            None => lines.push(unset(shell, name)),
+        }
+    }
+
+    for (name, value) in vars {
+        // Variables the project already set keep the value saved back then
+        if !previous.contains(name) {
+            let saved = saved_var(name);
+            match current(name) {
+                Some(value) => lines.push(export(shell, &saved, &value)),
+                None if current(&saved).is_some() => lines.push(unset(shell, &saved)),
+                None => {}
+            }
+        }
+        lines.push(export(shell, name, value));
+    }
+
+    let names: Vec<&str> = vars.iter().map(|(name, _)| name.as_str()).collect();
+    if names.is_empty() {
+        lines.push(unset(shell, DIR_VARS_VAR));
+    } else {
+        lines.push(export(shell, DIR_VARS_VAR, &names.join(",")));
+    }
+
+    match state {
+        Some(state) => lines.push(export(shell, DIR_STATE_VAR, state)),
+        None => lines.push(unset(shell, DIR_STATE_VAR)),
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn saved_var(name: &str) -> String {
+    format!("{DIR_SAVED_PREFIX}{name}")
+}
+
+fn export(shell: HookShell, name: &str, value: &str) -> String {
+    match shell {
+        HookShell::Bash | HookShell::Zsh => format!("export {name}={};", quote(shell, value)),
+        HookShell::Fish => format!("set -gx {name} {};", quote(shell, value)),
+    }
+}
+
+fn unset(shell: HookShell, name: &str) -> String {
+    match shell {
+        HookShell::Bash | HookShell::Zsh => format!("unset {name};"),
+        HookShell::Fish => format!("set -e {name};"),
+    }
+}
+
+/// Single-quote a value for the shell
+pub fn quote(shell: HookShell, value: &str) -> String {
+    match shell {
+        HookShell::Bash | HookShell::Zsh => format!("'{}'", value.replace('\'', r"'\''")),
+        HookShell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+    }
+}
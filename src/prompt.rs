@@ -0,0 +1,175 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::aws::{parse_tag, ProfileInfo};
+use crate::duration::format_duration;
+use crate::shell::ShellKind;
+
+/// The `prompt` setting's default
+pub const DEFAULT_PROMPT: &str = "aws:{profile} ";
+
+/// Placeholders a prompt template may use
+pub const PLACEHOLDERS: [&str; 7] = [
+    "alias", "profile", "account", "role", "region", "session", "ttl",
+];
+
+/// How color codes are wrapped so the shell knows they take no space
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PromptShell {
+    Bash,
+    Zsh,
+    /// Bare escape codes, e.g. for fish or starship
+    #[default]
+    Plain,
+}
+
+impl PromptShell {
+    pub fn for_kind(kind: ShellKind) -> Self {
+        match kind {
+            ShellKind::Bash => PromptShell::Bash,
+            ShellKind::Zsh => PromptShell::Zsh,
+            _ => PromptShell::Plain,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    fn code(&self) -> u8 {
+        match self {
+            Color::Black => 30,
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            Color::White => 37,
+        }
+    }
+}
+
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    Color::from_str(value, true).map_err(|_| {
+        let names: Vec<String> = Color::value_variants()
+            .iter()
+            .filter_map(|color| color.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        format!("Invalid color '{value}' (one of {})", names.join(", "))
+    })
+}
+
+/// Every `{placeholder}` in the template must be known
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("Unclosed '{{' in prompt template '{template}'"));
+        };
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{name}}} (use {})",
+                PLACEHOLDERS.map(|name| format!("{{{name}}}")).join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Parse `KEY=VALUE=COLOR` entries separated by commas, e.g. `env=prod=red`
+pub fn parse_tag_colors(value: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut colors = BTreeMap::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((tag, color)) = entry.rsplit_once('=') else {
+            return Err(format!(
+                "Invalid tag color '{entry}' (expected KEY=VALUE=COLOR)"
+            ));
+        };
+        parse_tag(tag)?;
+        parse_color(color)?;
+        colors.insert(tag.to_string(), color.to_lowercase());
+    }
+    Ok(colors)
+}
+
+pub fn format_tag_colors(colors: &BTreeMap<String, String>) -> String {
+    let entries: Vec<String> = colors
+        .iter()
+        .map(|(tag, color)| format!("{tag}={color}"))
+        .collect();
+    entries.join(",")
+}
+
+/// The color of the first tag of the profile that has one
+pub fn tag_color(profile: &ProfileInfo, colors: &BTreeMap<String, String>) -> Option<Color> {
+    colors.iter().find_map(|(tag, color)| {
+        let (key, value) = parse_tag(tag).ok()?;
+        if profile.has_tag(&key, &value) {
+            parse_color(color).ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Time left on the profile's SSO token, in whole minutes, or `expired`
+pub fn format_ttl(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let Ok(left) = (expires_at - now).to_std() else {
+        return "expired".to_string();
+    };
+    if left.is_zero() {
+        return "expired".to_string();
+    }
+    if left < Duration::from_secs(60) {
+        return "<1m".to_string();
+    }
+    format_duration(Duration::from_secs(left.as_secs() / 60 * 60))
+}
+
+/// Fill in the template for a profile. `ttl` is empty when there is no token.
+pub fn render(template: &str, alias: &str, profile: &ProfileInfo, ttl: &str) -> String {
+    let region = profile.region.as_deref().unwrap_or(&profile.sso_region);
+    template
+        .replace("{alias}", alias)
+        .replace("{profile}", &profile.profile_name)
+        .replace("{account}", &profile.sso_account_id)
+        .replace("{role}", &profile.sso_role_name)
+        .replace("{region}", region)
+        .replace("{session}", &profile.session_name)
+        .replace("{ttl}", ttl)
+}
+
+/// Wrap text in a color, escaped for the shell's prompt. Trailing whitespace
+/// stays outside the color.
+pub fn colorize(text: &str, color: Color, shell: PromptShell) -> String {
+    let trimmed = text.trim_end();
+    let trailing = &text[trimmed.len()..];
+    let (start, end) = (format!("\x1b[{}m", color.code()), "\x1b[0m");
+    match shell {
+        PromptShell::Bash => format!("\\[{start}\\]{trimmed}\\[{end}\\]{trailing}"),
+        PromptShell::Zsh => format!("%{{{start}%}}{trimmed}%{{{end}%}}{trailing}"),
+        PromptShell::Plain => format!("{start}{trimmed}{end}{trailing}"),
+    }
+}
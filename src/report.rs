@@ -0,0 +1,162 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::aws::ProfileInfo;
+use crate::history::{Action, HistoryEntry};
+
+pub const UNTAGGED: &str = "(untagged)";
+
+type Interval = (DateTime<Utc>, DateTime<Utc>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Account,
+    Tag,
+    Profile,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+}
+
+/// A finished sub-shell session reconstructed from the history
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub profile: String,
+    pub account_id: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportRow {
+    pub group: String,
+    pub sessions: usize,
+    pub seconds: i64,
+}
+
+pub fn sessions_from_history(entries: &[HistoryEntry]) -> Vec<Session> {
+    entries
+        .iter()
+        .filter(|entry| entry.action == Action::Use)
+        .filter_map(|entry| {
+            // A corrupted duration skips the entry rather than overflowing
+            let secs = i64::try_from(entry.duration_secs?).ok()?;
+            let end = entry
+                .timestamp
+                .checked_add_signed(Duration::try_seconds(secs)?)?;
+            Some(Session {
+                profile: entry.profile.clone(),
+                account_id: entry.account_id.clone(),
+                start: entry.timestamp,
+                end,
+            })
+        })
+        .collect()
+}
+
+/// Union of the given intervals, sorted by start
+pub fn merge_intervals(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort();
+    let mut merged: Vec<Interval> = Vec::new();
+
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
+
+fn group_key(
+    session: &Session,
+    profiles: &HashMap<String, ProfileInfo>,
+    by: GroupBy,
+    tag_key: &str,
+) -> String {
+    let profile = profiles.get(&session.profile);
+    match by {
+        GroupBy::Profile => session.profile.clone(),
+        GroupBy::Account => session
+            .account_id
+            .clone()
+            .or_else(|| profile.map(|info| info.sso_account_id.clone()))
+            .unwrap_or_else(|| "(unknown)".to_string()),
+        GroupBy::Tag => profile
+            .and_then(|info| info.tags.get(tag_key).cloned())
+            .unwrap_or_else(|| UNTAGGED.to_string()),
+    }
+}
+
+/// Total the active time per group within `[since, until)`.
+/// Overlapping sessions within a group (parallel terminals) are only counted once.
+pub fn build_report(
+    sessions: &[Session],
+    profiles: &HashMap<String, ProfileInfo>,
+    by: GroupBy,
+    tag_key: &str,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Vec<ReportRow> {
+    let mut groups: BTreeMap<String, (usize, Vec<Interval>)> = BTreeMap::new();
+
+    for session in sessions {
+        let start = since.map_or(session.start, |since| session.start.max(since));
+        let end = until.map_or(session.end, |until| session.end.min(until));
+        if start >= end {
+            continue;
+        }
+
+        let group = groups
+            .entry(group_key(session, profiles, by, tag_key))
+            .or_default();
+        group.0 += 1;
+        group.1.push((start, end));
+    }
+
+    groups
+        .into_iter()
+        .map(|(group, (sessions, intervals))| ReportRow {
+            group,
+            sessions,
+            seconds: merge_intervals(intervals)
+                .iter()
+                .map(|(start, end)| (*end - *start).num_seconds())
+                .sum(),
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn render_csv(rows: &[ReportRow], by: GroupBy) -> String {
+    let header = match by {
+        GroupBy::Account => "account",
+        GroupBy::Tag => "tag",
+        GroupBy::Profile => "profile",
+    };
+    let mut output = format!("{header},sessions,seconds,hours\n");
+    for row in rows {
+        output.push_str(&format!(
+            "{},{},{},{:.2}\n",
+            csv_field(&row.group),
+            row.sessions,
+            row.seconds,
+            row.seconds as f64 / 3600.0
+        ));
+    }
+    output
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use crate::aws::ProfileInfo;
+use crate::doctor::AwsSections;
+
+pub const DEFAULT_SCOPES: &str = "sso:account:access";
+
+/// An `[sso-session]` section of `~/.aws/config`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SsoSession {
+    pub name: String,
+    pub start_url: String,
+    pub region: String,
+    pub scopes: Option<String>,
+}
+
+impl SsoSession {
+    pub fn section_name(&self) -> String {
+        format!("sso-session {}", self.name)
+    }
+
+    pub fn section_values(&self) -> Vec<(&str, &str)> {
+        let mut values = vec![
+            ("sso_start_url", self.start_url.as_str()),
+            ("sso_region", self.region.as_str()),
+        ];
+        if let Some(scopes) = &self.scopes {
+            values.push(("sso_registration_scopes", scopes.as_str()));
+        }
+        values
+    }
+}
+
+/// All `[sso-session]` sections, sorted by name
+pub fn read_sessions(sections: &AwsSections) -> Vec<SsoSession> {
+    let value = |section: &HashMap<String, Option<String>>, key: &str| {
+        section
+            .get(key)
+            .and_then(|value| value.clone())
+            .unwrap_or_default()
+    };
+
+    let mut sessions: Vec<SsoSession> = sections
+        .iter()
+        .filter_map(|(name, section)| {
+            let name = name.strip_prefix("sso-session ")?;
+            Some(SsoSession {
+                name: name.to_string(),
+                start_url: value(section, "sso_start_url"),
+                region: value(section, "sso_region"),
+                scopes: section
+                    .get("sso_registration_scopes")
+                    .and_then(|value| value.clone()),
+            })
+        })
+        .collect();
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    sessions
+}
+
+/// Aliases of the profiles using a session, sorted
+pub fn dependent_profiles(profiles: &HashMap<String, ProfileInfo>, session: &str) -> Vec<String> {
+    let mut aliases: Vec<String> = profiles
+        .iter()
+        .filter(|(_, info)| info.session_name == session)
+        .map(|(alias, _)| alias.clone())
+        .collect();
+    aliases.sort();
+    aliases
+}
+
+pub fn validate_session_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '[' || c == ']') {
+        return Err(format!(
+            "Invalid session name '{name}' (no spaces or brackets)"
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_start_url(url: &str) -> Result<(), String> {
+    match url.strip_prefix("https://") {
+        Some(host) if !host.is_empty() => Ok(()),
+        _ => Err(format!(
+            "Invalid start URL '{url}' (e.g. https://acme.awsapps.com/start)"
+        )),
+    }
+}
+
+/// `[profile]` sections that use the session and also carry their own copy of
+/// the start URL or region, which must follow when the session moves
+pub fn profile_sections_with_copies(sections: &AwsSections, session: &str) -> Vec<String> {
+    let mut names: Vec<String> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("profile "))
+        .filter(|(_, section)| {
+            section
+                .get("sso_session")
+                .and_then(|value| value.as_deref())
+                == Some(session)
+                && (section.contains_key("sso_start_url") || section.contains_key("sso_region"))
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    names
+}
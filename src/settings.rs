@@ -0,0 +1,284 @@
+#![allow(dead_code)]
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::prompt;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        }
+    }
+}
+
+/// The keys of `~/.kee/settings.toml`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Key {
+    /// Shell started by `kee use` (defaults to $SHELL)
+    Shell,
+    /// Prompt prefix template, e.g. [{alias}|{role}@{region} {ttl}]
+    Prompt,
+    /// Prompt colors by tag, e.g. env=prod=red,env=dev=green
+    #[value(name = "prompt_colors")]
+    PromptColors,
+    /// Whether to use colors: auto, always or never
+    Color,
+    /// Answer to confirmation prompts when Enter is pressed
+    #[value(name = "confirm_default")]
+    ConfirmDefault,
+    /// The AWS CLI program
+    #[value(name = "aws_bin")]
+    AwsBin,
+}
+
+impl Key {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Key::Shell => "shell",
+            Key::Prompt => "prompt",
+            Key::PromptColors => "prompt_colors",
+            Key::Color => "color",
+            Key::ConfirmDefault => "confirm_default",
+            Key::AwsBin => "aws_bin",
+        }
+    }
+
+    /// The environment variable overriding the file
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Key::Shell => "KEE_SHELL",
+            Key::Prompt => "KEE_PROMPT",
+            Key::PromptColors => "KEE_PROMPT_COLORS",
+            Key::Color => "KEE_COLOR",
+            Key::ConfirmDefault => "KEE_CONFIRM_DEFAULT",
+            Key::AwsBin => "KEE_AWS_BIN",
+        }
+    }
+}
+
+/// Where an effective setting comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::File => "settings.toml",
+            Source::Env => "environment",
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Ok(true),
+        "false" | "no" | "n" | "0" => Ok(false),
+        _ => Err(format!("Invalid value '{value}' (expected true or false)")),
+    }
+}
+
+fn parse_color(value: &str) -> Result<ColorChoice, String> {
+    ColorChoice::from_str(value, true)
+        .map_err(|_| format!("Invalid color '{value}' (expected auto, always or never)"))
+}
+
+fn non_empty(key: Key, value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err(format!("{} cannot be empty", key.name()));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_prompt(value: &str) -> Result<String, String> {
+    prompt::validate_template(value)?;
+    non_empty(Key::Prompt, value)
+}
+
+/// The contents of `~/.kee/settings.toml`; unset keys use the defaults
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_default: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws_bin: Option<String>,
+    /// A table, so it goes last in the TOML file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_colors: Option<BTreeMap<String, String>>,
+}
+
+impl SettingsFile {
+    pub fn get(&self, key: Key) -> Option<String> {
+        match key {
+            Key::Shell => self.shell.clone(),
+            Key::Prompt => self.prompt.clone(),
+            Key::PromptColors => self.prompt_colors.as_ref().map(prompt::format_tag_colors),
+            Key::Color => self.color.map(|color| color.as_str().to_string()),
+            Key::ConfirmDefault => self.confirm_default.map(|value| value.to_string()),
+            Key::AwsBin => self.aws_bin.clone(),
+        }
+    }
+
+    /// Validate and store a value, or remove the key with `None`
+    pub fn set(&mut self, key: Key, value: Option<&str>) -> Result<(), String> {
+        match key {
+            Key::Shell => self.shell = value.map(|v| non_empty(key, v)).transpose()?,
+            Key::Prompt => self.prompt = value.map(parse_prompt).transpose()?,
+            Key::PromptColors => {
+                self.prompt_colors = value.map(prompt::parse_tag_colors).transpose()?
+            }
+            Key::Color => self.color = value.map(parse_color).transpose()?,
+            Key::ConfirmDefault => self.confirm_default = value.map(parse_bool).transpose()?,
+            Key::AwsBin => self.aws_bin = value.map(|v| non_empty(key, v)).transpose()?,
+        }
+        Ok(())
+    }
+}
+
+/// The effective settings: environment variables over the file over the defaults.
+/// CLI flags are applied on top by the commands that have them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// `None` uses `$SHELL` (`%COMSPEC%` on Windows)
+    pub shell: Option<String>,
+    pub prompt: String,
+    pub prompt_colors: BTreeMap<String, String>,
+    pub color: ColorChoice,
+    pub confirm_default: bool,
+    pub aws_bin: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            prompt: prompt::DEFAULT_PROMPT.to_string(),
+            prompt_colors: BTreeMap::new(),
+            color: ColorChoice::Auto,
+            confirm_default: false,
+            aws_bin: "aws".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// Resolve the settings from the file and the environment. Invalid values
+    /// from either are skipped and reported as warnings.
+    pub fn resolve(
+        file: &SettingsFile,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> (Self, Vec<String>) {
+        let mut settings = Settings::default();
+        let mut warnings = Vec::new();
+
+        for key in Key::value_variants() {
+            if let Some(value) = file.get(*key) {
+                if let Err(e) = settings.apply(*key, &value) {
+                    warnings.push(format!("Ignoring {} in the settings file: {e}", key.name()));
+                }
+            }
+            if let Some(value) = env(key.env_var()).filter(|value| !value.is_empty()) {
+                if let Err(e) = settings.apply(*key, &value) {
+                    warnings.push(format!("Ignoring {}: {e}", key.env_var()));
+                }
+            }
+        }
+        (settings, warnings)
+    }
+
+    pub fn apply(&mut self, key: Key, value: &str) -> Result<(), String> {
+        match key {
+            Key::Shell => self.shell = Some(non_empty(key, value)?),
+            Key::Prompt => self.prompt = parse_prompt(value)?,
+            Key::PromptColors => self.prompt_colors = prompt::parse_tag_colors(value)?,
+            Key::Color => self.color = parse_color(value)?,
+            Key::ConfirmDefault => self.confirm_default = parse_bool(value)?,
+            Key::AwsBin => self.aws_bin = non_empty(key, value)?,
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: Key) -> String {
+        match key {
+            Key::Shell => self.shell.clone().unwrap_or_else(|| "$SHELL".to_string()),
+            Key::Prompt => self.prompt.clone(),
+            Key::PromptColors => prompt::format_tag_colors(&self.prompt_colors),
+            Key::Color => self.color.as_str().to_string(),
+            Key::ConfirmDefault => self.confirm_default.to_string(),
+            Key::AwsBin => self.aws_bin.clone(),
+        }
+    }
+}
+
+/// Where a key's effective value comes from
+pub fn source(file: &SettingsFile, env: impl Fn(&str) -> Option<String>, key: Key) -> Source {
+    if env(key.env_var()).is_some_and(|value| !value.is_empty()) {
+        Source::Env
+    } else if file.get(key).is_some() {
+        Source::File
+    } else {
+        Source::Default
+    }
+}
+
+/// `~/.kee/settings.toml`
+pub struct SettingsStore {
+    path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn load(&self) -> io::Result<SettingsFile> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", self.path.display(), e.message()),
+                )
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SettingsFile::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, file: &SettingsFile) -> io::Result<()> {
+        let content =
+            toml::to_string(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(&self.path, content)
+    }
+}
@@ -0,0 +1,171 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Holds the prompt prefix; the generated rc files read it from here so they
+/// never have to quote profile names
+pub const PROMPT_PREFIX_VAR: &str = "KEE_PROMPT_PREFIX";
+const ORIG_ZDOTDIR_VAR: &str = "KEE_ORIG_ZDOTDIR";
+/// Set when the prompt changes over time, e.g. with `{ttl}`: bash and zsh then
+/// ask this kee for the prefix before every prompt instead of using a fixed one
+pub const PROMPT_EXE_VAR: &str = "KEE_PROMPT_EXE";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    PowerShell,
+    /// Any other shell, which only gets `PS1`
+    Other,
+}
+
+impl ShellKind {
+    /// Detect the shell from its path, e.g. `/usr/bin/zsh` or `pwsh.exe`
+    pub fn detect(shell: &str) -> Self {
+        let name = Path::new(shell)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            "fish" => ShellKind::Fish,
+            "nu" => ShellKind::Nushell,
+            "pwsh" | "powershell" => ShellKind::PowerShell,
+            _ => ShellKind::Other,
+        }
+    }
+}
+
+/// Arguments and environment that make a shell show the prompt prefix
+#[derive(Debug, Default, PartialEq)]
+pub struct Bootstrap {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+/// Sources the user's `~/.bashrc` first, so the prefix survives whatever it does to `PS1`.
+/// A live prefix replaces the last one after the user's `PROMPT_COMMAND` ran.
+pub const BASH_RCFILE: &str = r#"if [ -f "$HOME/.bashrc" ]; then
+    . "$HOME/.bashrc"
+fi
+if [ -n "$KEE_PROMPT_EXE" ]; then
+    _kee_prefix=""
+    _kee_prompt() {
+        local prefix
+        prefix="$("$KEE_PROMPT_EXE" prompt --shell bash)"
+        PS1="${prefix}${PS1#"$_kee_prefix"}"
+        _kee_prefix="$prefix"
+    }
+    PROMPT_COMMAND="${PROMPT_COMMAND:+$PROMPT_COMMAND;}_kee_prompt"
+else
+    PS1="${KEE_PROMPT_PREFIX}${PS1}"
+fi
+"#;
+
+/// zsh reads `.zshenv` and `.zshrc` from `$ZDOTDIR`; these forward to the user's own
+/// files and switch `ZDOTDIR` back before `.zshrc` so plugins find their files
+pub const ZSHENV: &str = r#"KEE_ZDOTDIR="$ZDOTDIR"
+ZDOTDIR="${KEE_ORIG_ZDOTDIR:-$HOME}"
+if [ -f "$ZDOTDIR/.zshenv" ]; then
+    source "$ZDOTDIR/.zshenv"
+fi
+ZDOTDIR="$KEE_ZDOTDIR"
+unset KEE_ZDOTDIR
+"#;
+
+pub const ZSHRC: &str = r#"ZDOTDIR="${KEE_ORIG_ZDOTDIR:-$HOME}"
+unset KEE_ORIG_ZDOTDIR
+if [ -f "$ZDOTDIR/.zshrc" ]; then
+    source "$ZDOTDIR/.zshrc"
+fi
+if [ -n "$KEE_PROMPT_EXE" ]; then
+    _kee_prefix=""
+    _kee_prompt() {
+        local prefix
+        prefix="$("$KEE_PROMPT_EXE" prompt --shell zsh)"
+        PROMPT="${prefix}${PROMPT#"$_kee_prefix"}"
+        _kee_prefix="$prefix"
+    }
+    typeset -ag precmd_functions
+    precmd_functions+=(_kee_prompt)
+else
+    PROMPT="${KEE_PROMPT_PREFIX}${PROMPT}"
+fi
+"#;
+
+/// Runs after `config.fish`, wrapping whatever `fish_prompt` it defined
+pub const FISH_INIT: &str = "functions -q fish_prompt; and functions -c fish_prompt _kee_fish_prompt; \
+function fish_prompt; printf '%s' $KEE_PROMPT_PREFIX; functions -q _kee_fish_prompt; and _kee_fish_prompt; end";
+
+/// Runs after the user's `config.nu`; `PROMPT_COMMAND` may be a closure or a string
+pub const NUSHELL_INIT: &str = "let kee_prompt = ($env.PROMPT_COMMAND? | default ''); \
+$env.PROMPT_COMMAND = {|| $env.KEE_PROMPT_PREFIX + (if ($kee_prompt | describe) == 'string' { $kee_prompt } else { do $kee_prompt }) }";
+
+/// Runs after the user's profile, wrapping the `prompt` function
+pub const POWERSHELL_INIT: &str = "$global:KeePrompt = $function:prompt; \
+function global:prompt { \"$env:KEE_PROMPT_PREFIX\" + (& $global:KeePrompt) }";
+
+/// Prepare a shell to show `prefix` in its prompt. Files needed by bash and zsh
+/// are written to `dir`, which must exist, be private to the user and outlive
+/// the shell.
+pub fn bootstrap(
+    kind: ShellKind,
+    prefix: &str,
+    dir: &Path,
+    current_ps1: Option<&str>,
+) -> io::Result<Bootstrap> {
+    let mut bootstrap = Bootstrap::default();
+    bootstrap
+        .env
+        .push((PROMPT_PREFIX_VAR.to_string(), prefix.to_string()));
+
+    match kind {
+        ShellKind::Bash => {
+            let rcfile = dir.join("bashrc");
+            fs::write(&rcfile, BASH_RCFILE)?;
+            bootstrap.args = vec!["--rcfile".to_string(), rcfile.to_string_lossy().to_string()];
+        }
+        ShellKind::Zsh => {
+            let zdotdir = dir.join("zsh");
+            fs::create_dir(&zdotdir)?;
+            fs::write(zdotdir.join(".zshenv"), ZSHENV)?;
+            fs::write(zdotdir.join(".zshrc"), ZSHRC)?;
+            bootstrap.env.push((
+                ORIG_ZDOTDIR_VAR.to_string(),
+                std::env::var("ZDOTDIR").unwrap_or_default(),
+            ));
+            bootstrap
+                .env
+                .push(("ZDOTDIR".to_string(), zdotdir.to_string_lossy().to_string()));
+        }
+        ShellKind::Fish => {
+            bootstrap.args = vec!["--init-command".to_string(), FISH_INIT.to_string()];
+        }
+        ShellKind::Nushell => {
+            bootstrap.args = vec!["--execute".to_string(), NUSHELL_INIT.to_string()];
+        }
+        ShellKind::PowerShell => {
+            bootstrap.args = vec![
+                "-NoLogo".to_string(),
+                "-NoExit".to_string(),
+                "-Command".to_string(),
+                POWERSHELL_INIT.to_string(),
+            ];
+        }
+        ShellKind::Other if cfg!(windows) => {}
+        ShellKind::Other => {
+            let ps1 = current_ps1.unwrap_or("$ ");
+            bootstrap
+                .env
+                .push(("PS1".to_string(), format!("{prefix}{ps1}")));
+        }
+    }
+
+    Ok(bootstrap)
+}
@@ -0,0 +1,254 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::credentials::Credentials;
+
+/// AWS Signature Version 4, see
+/// <https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html>
+pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// The payload hash of presigned S3 URLs, whose body is not known when signing
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but the unreserved characters, as SigV4 requires.
+/// Slashes stay as they are in paths.
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Query parameters encoded and sorted by name, then value
+pub fn canonical_query(params: &[(String, String)]) -> String {
+    let mut encoded: Vec<(String, String)> = params
+        .iter()
+        .map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+        .collect();
+    encoded.sort();
+    encoded
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Header names lowercased, values trimmed, sorted by name
+fn normalize_headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    let mut normalized: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    normalized.sort();
+    normalized
+}
+
+/// The `SignedHeaders` value: the lowercased header names, sorted, joined by `;`
+pub fn signed_headers(headers: &[(&str, &str)]) -> String {
+    normalize_headers(headers)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+pub fn canonical_request(
+    method: &str,
+    path: &str,
+    query: &[(String, String)],
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let canonical_headers: String = normalize_headers(headers)
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+    format!(
+        "{method}\n{}\n{}\n{canonical_headers}\n{}\n{payload_hash}",
+        uri_encode(path, false),
+        canonical_query(query),
+        signed_headers(headers)
+    )
+}
+
+/// The signing key, derived from the secret for a day, region and service
+pub fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{secret_access_key}").as_bytes(), date);
+    let key = hmac_sha256(&key, region);
+    let key = hmac_sha256(&key, service);
+    hmac_sha256(&key, "aws4_request")
+}
+
+/// Signs requests with temporary or long-term credentials
+pub struct Signer<'a> {
+    pub credentials: &'a Credentials,
+    pub region: &'a str,
+    pub service: &'a str,
+    pub time: DateTime<Utc>,
+}
+
+impl<'a> Signer<'a> {
+    pub fn new(
+        credentials: &'a Credentials,
+        region: &'a str,
+        service: &'a str,
+        time: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            credentials,
+            region,
+            service,
+            time,
+        }
+    }
+
+    /// `X-Amz-Date`, e.g. 20130524T000000Z
+    pub fn amz_date(&self) -> String {
+        self.time.format("%Y%m%dT%H%M%SZ").to_string()
+    }
+
+    fn date(&self) -> String {
+        self.time.format("%Y%m%d").to_string()
+    }
+
+    pub fn scope(&self) -> String {
+        format!(
+            "{}/{}/{}/aws4_request",
+            self.date(),
+            self.region,
+            self.service
+        )
+    }
+
+    pub fn string_to_sign(&self, canonical_request: &str) -> String {
+        format!(
+            "{ALGORITHM}\n{}\n{}\n{}",
+            self.amz_date(),
+            self.scope(),
+            sha256_hex(canonical_request.as_bytes())
+        )
+    }
+
+    /// The hex signature of a canonical request
+    pub fn signature(&self, canonical_request: &str) -> String {
+        let key = signing_key(
+            &self.credentials.secret_access_key,
+            &self.date(),
+            self.region,
+            self.service,
+        );
+        hex::encode(hmac_sha256(&key, &self.string_to_sign(canonical_request)))
+    }
+
+    /// The headers to add to a request to sign it: `X-Amz-Date`,
+    /// `X-Amz-Security-Token` for temporary credentials, and `Authorization`.
+    /// `headers` must include `host`; all of them are signed.
+    pub fn sign_headers(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        payload_hash: &str,
+    ) -> Vec<(&'static str, String)> {
+        let mut added = vec![("X-Amz-Date", self.amz_date())];
+        if let Some(token) = &self.credentials.session_token {
+            added.push(("X-Amz-Security-Token", token.clone()));
+        }
+
+        let mut all: Vec<(&str, &str)> = headers.to_vec();
+        all.extend(added.iter().map(|(name, value)| (*name, value.as_str())));
+        let params: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        let signature = self.signature(&canonical_request(
+            method,
+            path,
+            &params,
+            &all,
+            payload_hash,
+        ));
+        added.push((
+            "Authorization",
+            format!(
+                "{ALGORITHM} Credential={}/{}, SignedHeaders={}, Signature={signature}",
+                self.credentials.access_key_id,
+                self.scope(),
+                signed_headers(&all)
+            ),
+        ));
+        added
+    }
+
+    /// A URL that performs the request without further credentials until it
+    /// expires. `headers` must include `host` and are signed as well; the
+    /// client has to send them unchanged.
+    pub fn presign_url(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        expires_secs: u64,
+        payload_hash: &str,
+    ) -> String {
+        let host = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+            .map(|(_, value)| *value)
+            .expect("presigned requests sign the host header");
+
+        let mut params: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        params.extend([
+            ("X-Amz-Algorithm".to_string(), ALGORITHM.to_string()),
+            (
+                "X-Amz-Credential".to_string(),
+                format!("{}/{}", self.credentials.access_key_id, self.scope()),
+            ),
+            ("X-Amz-Date".to_string(), self.amz_date()),
+            ("X-Amz-Expires".to_string(), expires_secs.to_string()),
+            ("X-Amz-SignedHeaders".to_string(), signed_headers(headers)),
+        ]);
+        if let Some(token) = &self.credentials.session_token {
+            params.push(("X-Amz-Security-Token".to_string(), token.clone()));
+        }
+
+        let signature = self.signature(&canonical_request(
+            method,
+            path,
+            &params,
+            headers,
+            payload_hash,
+        ));
+        format!(
+            "https://{host}{}?{}&X-Amz-Signature={signature}",
+            uri_encode(path, false),
+            canonical_query(&params)
+        )
+    }
+}
@@ -0,0 +1,255 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::aws::ProfileInfo;
+use crate::credentials::Credentials;
+
+/// An SSO access token as cached by the AWS CLI in `~/.aws/sso/cache`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoToken {
+    pub access_token: String,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_url: Option<String>,
+}
+
+impl SsoToken {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// The AWS CLI names cache files after the SHA-1 of the sso-session name,
+/// or of the start URL for legacy profiles without a session.
+pub fn cache_key(session_name: &str, start_url: &str) -> String {
+    let input = if session_name.is_empty() {
+        start_url
+    } else {
+        session_name
+    };
+
+    let mut hasher = Sha1::new();
+    hasher.update(input.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn token_path(cache_dir: &Path, profile: &ProfileInfo) -> PathBuf {
+    cache_dir.join(format!(
+        "{}.json",
+        cache_key(&profile.session_name, &profile.sso_start_url)
+    ))
+}
+
+pub fn session_token_path(cache_dir: &Path, session_name: &str) -> PathBuf {
+    cache_dir.join(format!("{}.json", cache_key(session_name, "")))
+}
+
+pub fn read_token(path: &Path) -> io::Result<SsoToken> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The AWS CLI names cached role credentials after the SHA-1 of the compact,
+/// key-sorted JSON of the account, role and session (or legacy start URL)
+pub fn role_cache_key(profile: &ProfileInfo) -> String {
+    let mut args = std::collections::BTreeMap::new();
+    args.insert("accountId", profile.sso_account_id.as_str());
+    args.insert("roleName", profile.sso_role_name.as_str());
+    if profile.session_name.is_empty() {
+        args.insert("startUrl", profile.sso_start_url.as_str());
+    } else {
+        args.insert("sessionName", profile.session_name.as_str());
+    }
+
+    let mut hasher = Sha1::new();
+    hasher.update(serde_json::to_string(&args).unwrap_or_default().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn role_cache_path(cli_cache_dir: &Path, profile: &ProfileInfo) -> PathBuf {
+    cli_cache_dir.join(format!("{}.json", role_cache_key(profile)))
+}
+
+/// Every cached SSO token, skipping the client registrations stored alongside them
+pub fn cached_tokens(cache_dir: &Path) -> Vec<(PathBuf, SsoToken)> {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+
+    let mut tokens: Vec<(PathBuf, SsoToken)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| read_token(&path).ok().map(|token| (path, token)))
+        .collect();
+    tokens.sort_by(|a, b| a.0.cmp(&b.0));
+    tokens
+}
+
+/// Role credentials the AWS CLI caches in `~/.aws/cli/cache` after an SSO login
+pub fn is_sso_role_credentials(content: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(content)
+        .is_ok_and(|value| value["ProviderType"] == "sso")
+}
+
+pub fn role_credential_caches(cli_cache_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(cli_cache_dir) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path).is_ok_and(|content| is_sso_role_credentials(&content))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Replaces the SSO portal endpoint, as it does for the AWS SDKs and CLI
+pub const PORTAL_ENDPOINT_VAR: &str = "AWS_ENDPOINT_URL_SSO";
+
+/// Like the AWS CLI, cached role credentials are renewed this long before they expire
+pub const EXPIRY_WINDOW_MINUTES: i64 = 15;
+
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn portal_endpoint(region: &str) -> String {
+    format!("https://portal.sso.{region}.amazonaws.com")
+}
+
+/// The portal endpoint of a region, unless `portal` replaces it
+pub fn resolve_portal_endpoint(region: &str, portal: Option<&str>) -> String {
+    match portal {
+        Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+        None => portal_endpoint(region),
+    }
+}
+
+/// Revoke a token with the SSO portal's Logout API
+pub fn portal_logout(endpoint: &str, access_token: &str) -> Result<(), String> {
+    ureq::post(&format!("{endpoint}/logout"))
+        .timeout(PORTAL_TIMEOUT)
+        .set("x-amz-sso_bearer_token", access_token)
+        .call()
+        .map(|_| ())
+        .map_err(|e| format!("The SSO portal did not accept the logout: {e}"))
+}
+
+#[derive(Deserialize)]
+struct CachedRoleCredentials {
+    #[serde(rename = "Credentials")]
+    credentials: Credentials,
+}
+
+/// The profile's role credentials from the AWS CLI cache, unless they expire
+/// within the expiry window
+pub fn cached_role_credentials(
+    cli_cache_dir: &Path,
+    profile: &ProfileInfo,
+    now: DateTime<Utc>,
+) -> Option<Credentials> {
+    let content = fs::read_to_string(role_cache_path(cli_cache_dir, profile)).ok()?;
+    let cached: CachedRoleCredentials = serde_json::from_str(&content).ok()?;
+    let expiration = cached.credentials.expiration?;
+    (expiration - chrono::Duration::minutes(EXPIRY_WINDOW_MINUTES) > now)
+        .then_some(cached.credentials)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    /// Milliseconds since the epoch
+    expiration: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetRoleCredentials {
+    role_credentials: RoleCredentials,
+}
+
+/// The credentials of the portal's `GetRoleCredentials` response
+pub fn role_credentials_from_json(output: &str) -> Result<Credentials, String> {
+    let response: GetRoleCredentials =
+        serde_json::from_str(output).map_err(|e| format!("Unexpected role credentials: {e}"))?;
+    let role = response.role_credentials;
+    Ok(Credentials {
+        access_key_id: role.access_key_id,
+        secret_access_key: role.secret_access_key,
+        session_token: Some(role.session_token),
+        expiration: DateTime::from_timestamp_millis(role.expiration),
+    })
+}
+
+/// Exchange an SSO access token for the role credentials of an account
+pub fn fetch_role_credentials(
+    endpoint: &str,
+    access_token: &str,
+    account_id: &str,
+    role_name: &str,
+) -> Result<Credentials, String> {
+    let response = ureq::get(&format!("{endpoint}/federation/credentials"))
+        .timeout(PORTAL_TIMEOUT)
+        .set("x-amz-sso_bearer_token", access_token)
+        .query("account_id", account_id)
+        .query("role_name", role_name)
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(401 | 403, _) => {
+                "The SSO portal did not accept the cached token".to_string()
+            }
+            e => format!("Could not get role credentials from the SSO portal: {e}"),
+        })?
+        .into_string()
+        .map_err(|e| format!("Could not read the role credentials: {e}"))?;
+    role_credentials_from_json(&response)
+}
+
+/// The profile's role credentials without the AWS CLI: the ones it cached
+/// while they last, otherwise fresh ones from the SSO portal for the cached
+/// token. `portal` replaces the token region's portal endpoint. `None` when
+/// there's no usable token, which only the AWS CLI can refresh.
+pub fn resolve_role_credentials(
+    sso_cache_dir: &Path,
+    cli_cache_dir: &Path,
+    profile: &ProfileInfo,
+    portal: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Option<Credentials>, String> {
+    if let Some(credentials) = cached_role_credentials(cli_cache_dir, profile, now) {
+        return Ok(Some(credentials));
+    }
+
+    let Ok(token) = read_token(&token_path(sso_cache_dir, profile)) else {
+        return Ok(None);
+    };
+    if token.is_expired(now) {
+        return Ok(None);
+    }
+    let endpoint = resolve_portal_endpoint(
+        token.region.as_deref().unwrap_or(&profile.sso_region),
+        portal,
+    );
+    fetch_role_credentials(
+        &endpoint,
+        &token.access_token,
+        &profile.sso_account_id,
+        &profile.sso_role_name,
+    )
+    .map(Some)
+}
@@ -0,0 +1,209 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use crate::credentials::Credentials;
+use crate::eks::sts_host;
+use crate::identity::CallerIdentity;
+use crate::sigv4::{self, Signer};
+
+/// The STS query API version, see
+/// <https://docs.aws.amazon.com/STS/latest/APIReference/CommonParameters.html>
+pub const VERSION: &str = "2011-06-15";
+
+/// Replaces the regional STS endpoint, as it does for the AWS SDKs and CLI
+pub const ENDPOINT_VAR: &str = "AWS_ENDPOINT_URL_STS";
+
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The parameters of an `AssumeRole` call
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssumeRole {
+    pub role_arn: String,
+    pub session_name: String,
+    pub external_id: Option<String>,
+    pub duration_secs: Option<u64>,
+    pub tags: Vec<(String, String)>,
+}
+
+impl AssumeRole {
+    pub fn params(&self) -> Vec<(String, String)> {
+        let mut params = vec![
+            ("Action".to_string(), "AssumeRole".to_string()),
+            ("RoleArn".to_string(), self.role_arn.clone()),
+            ("RoleSessionName".to_string(), self.session_name.clone()),
+        ];
+        if let Some(external_id) = &self.external_id {
+            params.push(("ExternalId".to_string(), external_id.clone()));
+        }
+        if let Some(duration) = self.duration_secs {
+            params.push(("DurationSeconds".to_string(), duration.to_string()));
+        }
+        for (index, (key, value)) in self.tags.iter().enumerate() {
+            let member = index + 1;
+            params.push((format!("Tags.member.{member}.Key"), key.clone()));
+            params.push((format!("Tags.member.{member}.Value"), value.clone()));
+        }
+        params
+    }
+}
+
+/// The text of the first `<tag>` element, unescaped. STS responses are small
+/// and flat, so looking up elements by name is all the parsing they need.
+pub fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(
+        xml[start..end]
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// `Code: Message` of an STS `ErrorResponse`
+pub fn error_message(xml: &str) -> Option<String> {
+    let code = xml_text(xml, "Code")?;
+    Some(match xml_text(xml, "Message") {
+        Some(message) => format!("{code}: {message}"),
+        None => code,
+    })
+}
+
+pub fn parse_caller_identity(xml: &str) -> Result<CallerIdentity, String> {
+    let field = |tag: &str| {
+        xml_text(xml, tag).ok_or_else(|| format!("Unexpected caller identity: no {tag}"))
+    };
+    Ok(CallerIdentity {
+        user_id: field("UserId")?,
+        account: field("Account")?,
+        arn: field("Arn")?,
+    })
+}
+
+/// The `Credentials` of an `AssumeRole` response
+pub fn parse_credentials(xml: &str) -> Result<Credentials, String> {
+    let field = |tag: &str| {
+        xml_text(xml, tag).ok_or_else(|| format!("Unexpected role credentials: no {tag}"))
+    };
+    let expiration = field("Expiration")?
+        .parse::<DateTime<Utc>>()
+        .map_err(|e| format!("Unexpected role credentials expiration: {e}"))?;
+    Ok(Credentials {
+        access_key_id: field("AccessKeyId")?,
+        secret_access_key: field("SecretAccessKey")?,
+        session_token: Some(field("SessionToken")?),
+        expiration: Some(expiration),
+    })
+}
+
+/// A minimal STS client speaking the query API over SigV4-signed POSTs
+#[derive(Clone, Debug, PartialEq)]
+pub struct StsClient {
+    endpoint: String,
+    region: String,
+}
+
+impl StsClient {
+    /// The client of the regional endpoint
+    pub fn new(region: &str) -> Self {
+        Self::with_endpoint(&format!("https://{}", sts_host(region)), region)
+    }
+
+    /// The client of another endpoint, e.g. a VPC endpoint or a local stand-in
+    pub fn with_endpoint(endpoint: &str, region: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: region.to_string(),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The endpoint's authority, which is signed as the `host` header
+    pub fn host(&self) -> &str {
+        let authority = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, rest)| rest);
+        authority.split('/').next().unwrap_or(authority)
+    }
+
+    /// The headers and form body of a signed request for `params`, which
+    /// name the action; the API version is added
+    pub fn signed_request(
+        &self,
+        params: &[(String, String)],
+        credentials: &Credentials,
+        time: DateTime<Utc>,
+    ) -> (Vec<(String, String)>, String) {
+        let mut params = params.to_vec();
+        params.push(("Version".to_string(), VERSION.to_string()));
+        let body = sigv4::canonical_query(&params);
+
+        let base = [("Content-Type", CONTENT_TYPE), ("Host", self.host())];
+        let signed = Signer::new(credentials, &self.region, "sts", time).sign_headers(
+            "POST",
+            "/",
+            &[],
+            &base,
+            &sigv4::sha256_hex(body.as_bytes()),
+        );
+
+        let headers = base
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .chain(
+                signed
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value)),
+            )
+            .collect();
+        (headers, body)
+    }
+
+    fn call(
+        &self,
+        params: &[(String, String)],
+        credentials: &Credentials,
+    ) -> Result<String, String> {
+        let (headers, body) = self.signed_request(params, credentials, Utc::now());
+        let mut request = ureq::post(&format!("{}/", self.endpoint)).timeout(TIMEOUT);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_string(&body) {
+            Ok(response) => response
+                .into_string()
+                .map_err(|e| format!("Could not read the STS response: {e}")),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                Err(error_message(&body)
+                    .unwrap_or_else(|| format!("STS responded with HTTP {status}")))
+            }
+            Err(e) => Err(format!("Could not reach STS: {e}")),
+        }
+    }
+
+    pub fn get_caller_identity(&self, credentials: &Credentials) -> Result<CallerIdentity, String> {
+        let params = [("Action".to_string(), "GetCallerIdentity".to_string())];
+        parse_caller_identity(&self.call(&params, credentials)?)
+    }
+
+    pub fn assume_role(
+        &self,
+        credentials: &Credentials,
+        assume_role: &AssumeRole,
+    ) -> Result<Credentials, String> {
+        parse_credentials(&self.call(&assume_role.params(), credentials)?)
+    }
+}
@@ -0,0 +1,54 @@
+#![allow(dead_code)]
+
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::settings::ColorChoice;
+
+const BOLD_WHITE: &str = "\x1b[1;37m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether `hlt` emits colors; off until main knows the color choice
+static COLORS: AtomicBool = AtomicBool::new(false);
+
+/// Whether to emit colors. With `auto`, a non-empty `NO_COLOR` turns them off,
+/// then a `CLICOLOR_FORCE` other than `0` turns them on, and otherwise they
+/// follow `terminal`. See <https://no-color.org> and <https://bixense.com/clicolors>.
+pub fn use_colors(
+    choice: ColorChoice,
+    env: impl Fn(&str) -> Option<String>,
+    terminal: bool,
+) -> bool {
+    let set = |name: &str| env(name).filter(|value| !value.is_empty());
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto if set("NO_COLOR").is_some() => false,
+        ColorChoice::Auto if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") => true,
+        ColorChoice::Auto => terminal,
+    }
+}
+
+/// Whether both output streams are terminals. Highlighted text is formatted
+/// before it is known which stream it goes to, so a pipe on either side turns
+/// colors off.
+pub fn is_terminal() -> bool {
+    io::stdout().is_terminal() && io::stderr().is_terminal()
+}
+
+pub fn set_colors(enabled: bool) {
+    COLORS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn colors() -> bool {
+    COLORS.load(Ordering::Relaxed)
+}
+
+/// Bold text, or the text unchanged when colors are off
+pub fn hlt(text: &str) -> String {
+    if colors() {
+        format!("{BOLD_WHITE}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
@@ -1,3 +1,8 @@
+#![allow(
+    clippy::single_component_path_imports,
+    clippy::needless_borrows_for_generic_args
+)]
+
 use serde_json;
 use std::fs;
 use std::process::Command;
@@ -52,8 +57,8 @@ mod integration_tests {
             .expect("Failed to execute list command");
 
         assert!(output.status.success());
-        let stdout = String::from_utf8(output.stdout).unwrap();
-        assert!(stdout.contains("No profiles configured"));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("No profiles configured"));
     }
 
     #[test]
@@ -67,8 +72,8 @@ mod integration_tests {
             .expect("Failed to execute list command with names flag");
 
         assert!(output.status.success());
-        let stdout = String::from_utf8(output.stdout).unwrap();
-        assert!(stdout.contains("No profiles configured"));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("No profiles configured"));
     }
 
     #[test]
@@ -82,8 +87,8 @@ mod integration_tests {
             .expect("Failed to execute current command");
 
         assert!(output.status.success());
-        let stdout = String::from_utf8(output.stdout).unwrap();
-        assert!(stdout.contains("No profile is currently active"));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("No profile is currently active"));
     }
 
     #[test]
@@ -111,8 +116,864 @@ mod integration_tests {
 
         // Command should succeed but show profile not found
         assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("not found"));
+    }
+
+    #[test]
+    fn test_use_invalid_duration() {
+        let output = Command::new("cargo")
+            .args(["run", "--", "use", "some-profile", "--for", "soon"])
+            .output()
+            .expect("Failed to execute use command");
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Invalid duration"));
+    }
+
+    #[test]
+    fn test_history_command_empty() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "history"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute history command");
+
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("No matching history entries"));
+    }
+
+    #[test]
+    fn test_history_command_json_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let kee_dir = temp_dir.path().join(".kee");
+        fs::create_dir_all(&kee_dir).unwrap();
+        fs::write(
+            kee_dir.join("history.jsonl"),
+            concat!(
+                r#"{"timestamp":"2026-10-01T10:00:00Z","action":"use","profile":"dev","duration_secs":60}"#,
+                "\n",
+                r#"{"timestamp":"2026-10-02T10:00:00Z","action":"rm","profile":"prod"}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "history", "--action", "use", "--json"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute history command");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let entries: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(entries.as_array().unwrap().len(), 1);
+        assert_eq!(entries[0]["profile"], "dev");
+    }
+
+    #[test]
+    fn test_report_command_csv() {
+        let temp_dir = TempDir::new().unwrap();
+        let kee_dir = temp_dir.path().join(".kee");
+        fs::create_dir_all(&kee_dir).unwrap();
+        fs::write(
+            kee_dir.join("history.jsonl"),
+            concat!(
+                r#"{"timestamp":"2026-10-01T10:00:00Z","action":"use","profile":"dev","account_id":"111111111111","duration_secs":3600}"#,
+                "\n",
+                r#"{"timestamp":"2026-10-01T10:30:00Z","action":"use","profile":"dev","account_id":"111111111111","duration_secs":3600}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "report",
+                "--since",
+                "2026-09-01",
+                "--by",
+                "account",
+                "--format",
+                "csv",
+            ])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute report command");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("111111111111,2,5400,1.50"));
+    }
+
+    #[test]
+    fn test_doctor_restores_missing_profile_section() {
+        let temp_dir = TempDir::new().unwrap();
+        let kee_dir = temp_dir.path().join(".kee");
+        fs::create_dir_all(&kee_dir).unwrap();
+        fs::create_dir_all(temp_dir.path().join(".aws")).unwrap();
+        fs::write(
+            kee_dir.join("config.json"),
+            r#"{"profiles":{"dev":{"profile_name":"dev","sso_start_url":"https://test.awsapps.com/start","sso_region":"us-east-1","sso_account_id":"111111111111","sso_role_name":"admin","session_name":"acme"}},"current_profile":null}"#,
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join(".aws").join("config"),
+            "[sso-session acme]\nsso_start_url = https://test.awsapps.com/start\nsso_region = us-east-1\n",
+        )
+        .unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "doctor"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute doctor command");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(!output.status.success());
+        assert!(stdout.contains("[profile dev] section is missing"));
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "doctor", "--fix"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute doctor command");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Fixed"));
+
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("[profile dev]"));
+        assert!(aws_config.contains("sso_account_id = 111111111111"));
+    }
+
+    fn write_profile_fixture(home: &std::path::Path) {
+        let kee_dir = home.join(".kee");
+        fs::create_dir_all(&kee_dir).unwrap();
+        fs::create_dir_all(home.join(".aws")).unwrap();
+        fs::write(
+            kee_dir.join("config.json"),
+            r#"{"profiles":{"dev":{"profile_name":"dev","sso_start_url":"https://test.awsapps.com/start","sso_region":"us-east-1","sso_account_id":"111111111111","sso_role_name":"Admin","session_name":"acme"}},"current_profile":"dev"}"#,
+        )
+        .unwrap();
+        fs::write(
+            home.join(".aws").join("config"),
+            "[profile dev]\nsso_session = acme\nsso_account_id = 111111111111\nsso_role_name = Admin\n\n[sso-session acme]\nsso_start_url = https://test.awsapps.com/start\nsso_region = us-east-1\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_trust_and_export_project_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join(".kee"), "# account for this repo\ndev\n").unwrap();
+
+        let export = || {
+            Command::new("cargo")
+                .args([
+                    "run",
+                    "--manifest-path",
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"),
+                    "--",
+                    "export",
+                    "bash",
+                ])
+                .current_dir(project.join("src"))
+                .env("HOME", temp_dir.path())
+                .env_remove("KEE_ACTIVE_PROFILE")
+                .env_remove("KEE_DIR_STATE")
+                .env_remove("KEE_DIR_VARS")
+                .output()
+                .expect("Failed to execute export command")
+        };
+
+        let output = export();
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("is not trusted"));
+        assert!(!String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("AWS_PROFILE"));
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "trust"])
+            .arg(&project)
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute trust command");
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("Trusted"));
+
+        let stdout = String::from_utf8(export().stdout).unwrap();
+        assert!(stdout.contains("export AWS_PROFILE='dev';"));
+        assert!(stdout.contains("export KEE_DIR_STATE="));
+    }
+
+    #[test]
+    fn test_completions_script() {
+        let output = Command::new("cargo")
+            .args(["run", "--", "completions", "bash"])
+            .output()
+            .expect("Failed to execute completions command");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("complete -o nospace -o bashdefault"));
+        assert!(stdout.contains("COMPLETE=\"bash\""));
+    }
+
+    #[test]
+    fn test_dynamic_profile_completion() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "--", "kee", "use", ""])
+            .env("HOME", temp_dir.path())
+            .env("COMPLETE", "fish")
+            .output()
+            .expect("Failed to execute completion request");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("dev\t111111111111 Admin"));
+    }
+
+    #[test]
+    fn test_session_set_url_updates_profiles() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "session",
+                "set-url",
+                "acme",
+                "https://new.awsapps.com/start",
+            ])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute session set-url command");
+
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("Updated profiles: dev"));
+
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("sso_start_url = https://new.awsapps.com/start"));
+        let config: KeeConfig = serde_json::from_str(
+            &fs::read_to_string(temp_dir.path().join(".kee").join("config.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.profiles["dev"].sso_start_url,
+            "https://new.awsapps.com/start"
+        );
+    }
+
+    #[test]
+    fn test_session_rm_refuses_used_session() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "session", "rm", "acme", "--yes"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute session rm command");
+
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("is used by: dev"));
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("[sso-session acme]"));
+    }
+
+    #[test]
+    fn test_logout_deletes_token_and_stale_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        // An expired token, so logging out doesn't call the SSO portal
+        let cache_dir = temp_dir.path().join(".aws").join("sso").join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        let token = cache_dir.join(format!("{}.json", kee::sso_cache::cache_key("acme", "")));
+        fs::write(
+            &token,
+            r#"{"accessToken":"secret","expiresAt":"2020-01-01T00:00:00Z","region":"us-east-1"}"#,
+        )
+        .unwrap();
+
+        // A sub-shell that no longer exists
+        let sessions_dir = temp_dir.path().join(".kee").join("sessions");
+        fs::create_dir_all(&sessions_dir).unwrap();
+        let stale = sessions_dir.join("4000000.json");
+        fs::write(
+            &stale,
+            r#"{"pid":4000000,"profile":"dev","sso_session":"acme","start_url":"","started":"2026-10-18T12:00:00Z"}"#,
+        )
+        .unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "logout", "acme"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute logout command");
+
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("Logged out of session"));
+        assert!(!token.exists());
+        assert!(!stale.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_each_prefixes_output_with_alias() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        // An aws CLI that accepts the credentials, so no login is attempted
+        let bin_dir = temp_dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("aws"), "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(bin_dir.join("aws"), fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            bin_dir.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "each",
+                "--all",
+                "--",
+                "sh",
+                "-c",
+                "echo $AWS_PROFILE",
+            ])
+            .env("HOME", temp_dir.path())
+            .env("PATH", path)
+            .output()
+            .expect("Failed to execute each command");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("dev | dev"));
+        assert!(stdout.contains("PROFILE  EXIT  TIME"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plugin_receives_profile_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let bin_dir = temp_dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        let plugin = bin_dir.join("kee-hello");
+        fs::write(
+            &plugin,
+            "#!/bin/sh\necho \"$1 $KEE_PROFILE_ALIAS $KEE_ACCOUNT_ID $AWS_PROFILE\"\n\"$KEE_BIN\" ls --json\nexit 3\n",
+        )
+        .unwrap();
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            bin_dir.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "hello", "world"])
+            .env("HOME", temp_dir.path())
+            .env("PATH", &path)
+            .env_remove("KEE_CURRENT_PROFILE")
+            .output()
+            .expect("Failed to execute plugin");
+
+        assert_eq!(output.status.code(), Some(3));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("world dev 111111111111 dev\n"));
+        let config: KeeConfig = serde_json::from_str(stdout.split_once('\n').unwrap().1).unwrap();
+        assert!(config.profiles.contains_key("dev"));
+
+        let help = Command::new("cargo")
+            .args(["run", "--", "help"])
+            .env("HOME", temp_dir.path())
+            .env("PATH", &path)
+            .output()
+            .expect("Failed to execute help command");
+        assert!(String::from_utf8(help.stdout)
+            .unwrap()
+            .contains("Plugins:\n  hello"));
+    }
+
+    #[test]
+    fn test_unknown_command_without_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "no-such-plugin"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute unknown command");
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("no kee-no-such-plugin plugin on PATH"));
+    }
+
+    #[test]
+    fn test_config_set_get_and_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = |args: &[&str], env: &[(&str, &str)]| {
+            Command::new("cargo")
+                .args(["run", "--", "config"])
+                .args(args)
+                .env("HOME", temp_dir.path())
+                .env_remove("KEE_AWS_BIN")
+                .envs(env.iter().copied())
+                .output()
+                .expect("Failed to execute config command")
+        };
+
+        assert!(config(&["set", "aws_bin", "/opt/aws"], &[])
+            .status
+            .success());
+        let settings =
+            fs::read_to_string(temp_dir.path().join(".kee").join("settings.toml")).unwrap();
+        assert_eq!(settings, "aws_bin = \"/opt/aws\"\n");
+
+        let output = config(&["get", "aws_bin"], &[]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "/opt/aws\n");
+        let output = config(&["get", "aws_bin"], &[("KEE_AWS_BIN", "/usr/bin/aws")]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "/usr/bin/aws\n");
+
+        let output = config(&["set", "confirm_default", "maybe"], &[]);
+        assert!(!output.status.success());
+        assert!(
+            !fs::read_to_string(temp_dir.path().join(".kee").join("settings.toml"))
+                .unwrap()
+                .contains("confirm_default")
+        );
+    }
+
+    #[test]
+    fn test_prompt_renders_active_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+        let prompt = |current: Option<&str>| {
+            let mut cmd = Command::new("cargo");
+            cmd.args(["run", "--", "prompt", "--shell", "bash"])
+                .env("HOME", temp_dir.path())
+                .env("KEE_PROMPT", "[{alias}|{role}@{region} {ttl}] ")
+                .env("KEE_PROMPT_COLORS", "env=prod=red")
+                .env_remove("KEE_CURRENT_PROFILE");
+            if let Some(current) = current {
+                cmd.env("KEE_CURRENT_PROFILE", current);
+            }
+            cmd.output().expect("Failed to execute prompt command")
+        };
+
+        // Nothing outside kee sub-shells
+        let output = prompt(None);
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+
+        // No token in the cache, so no TTL; no env=prod tag, so no color
+        let output = prompt(Some("dev"));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "[dev|Admin@us-east-1 ] "
+        );
+    }
+
+    #[test]
+    fn test_colors_follow_the_terminal_and_color_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let ls = |args: &[&str], env: &[(&str, &str)]| {
+            let output = Command::new("cargo")
+                .args(["run", "--"])
+                .args(args)
+                .env("HOME", temp_dir.path())
+                .env_remove("NO_COLOR")
+                .env_remove("CLICOLOR_FORCE")
+                .envs(env.iter().copied())
+                .output()
+                .expect("Failed to execute ls command");
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // Piped output has no escape codes
+        let stdout = ls(&["ls"], &[]);
+        assert!(stdout.contains("Account ID:"));
+        assert!(!stdout.contains('\x1b'));
+
+        assert!(ls(&["--color", "always", "ls"], &[]).contains("\x1b[1;37m"));
+        assert!(ls(&["ls"], &[("CLICOLOR_FORCE", "1")]).contains('\x1b'));
+        assert!(!ls(&["ls"], &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]).contains('\x1b'));
+        assert!(!ls(&["ls"], &[("CLICOLOR_FORCE", "0")]).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_diagnostics_go_to_stderr() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "rm", "missing", "--color", "always"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute remove command");
+
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("[!] Profile '\x1b[1;37mmissing\x1b[0m' not found."));
+    }
+
+    #[test]
+    fn test_docker_dry_run_prints_the_command() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "docker", "dev", "--dry-run", "--"])
+            .args(["run", "--rm", "amazon/aws-cli", "s3", "ls"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute docker command");
+
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "docker run -e AWS_ACCESS_KEY_ID -e AWS_SECRET_ACCESS_KEY -e AWS_SESSION_TOKEN \
+             -e AWS_CREDENTIAL_EXPIRATION -e AWS_REGION -e AWS_DEFAULT_REGION \
+             --rm amazon/aws-cli s3 ls\n"
+        );
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "docker", "dev", "--dry-run", "--agent", "--"])
+            .args(["run", "img"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute docker command");
         let stdout = String::from_utf8(output.stdout).unwrap();
-        assert!(stdout.contains("not found"));
+        assert!(
+            stdout.starts_with("docker run --network host -e AWS_CONTAINER_CREDENTIALS_FULL_URI")
+        );
+        assert!(!stdout.contains("AWS_ACCESS_KEY_ID"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_eks_token_is_signed_locally() {
+        use base64::Engine;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        // An aws CLI that only hands out credentials; the token needs no other call
+        let aws = temp_dir.path().join("aws");
+        fs::write(
+            &aws,
+            "#!/bin/sh\ncase \"$*\" in *export-credentials*) echo '{\"AccessKeyId\":\"AKID\",\"SecretAccessKey\":\"S\",\"SessionToken\":\"T\"}';; esac\n",
+        )
+        .unwrap();
+        fs::set_permissions(&aws, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "eks", "token", "--cluster", "prod", "dev"])
+            .env("HOME", temp_dir.path())
+            .env("KEE_AWS_BIN", &aws)
+            .output()
+            .expect("Failed to execute eks token command");
+
+        assert!(output.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(json["kind"], "ExecCredential");
+        let token = json["status"]["token"].as_str().unwrap();
+        let url = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(token.strip_prefix("k8s-aws-v1.").unwrap())
+            .unwrap();
+        let url = String::from_utf8(url).unwrap();
+        assert!(url.starts_with("https://sts.us-east-1.amazonaws.com/?Action=GetCallerIdentity"));
+        assert!(url.contains("X-Amz-Credential=AKID%2F"));
+        assert!(url.contains("X-Amz-Security-Token=T&"));
+    }
+
+    #[cfg(all(unix, feature = "native-sts"))]
+    #[test]
+    fn test_credentials_are_checked_with_sts_directly() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+        let cache_dir = temp_dir.path().join(".aws").join("sso").join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(
+            cache_dir.join(format!("{}.json", kee::sso_cache::cache_key("acme", ""))),
+            r#"{"accessToken":"sso-token","expiresAt":"2999-01-01T00:00:00Z","region":"us-east-1"}"#,
+        )
+        .unwrap();
+
+        // An aws CLI that leaves a trace, to show it's not needed
+        let called = temp_dir.path().join("aws-called");
+        let aws = temp_dir.path().join("aws");
+        fs::write(
+            &aws,
+            format!("#!/bin/sh\ntouch {}\nexit 1\n", called.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&aws, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // A stand-in for both the SSO portal and STS
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .map_or(0, |value| value.trim().parse().unwrap());
+                reader.read_exact(&mut vec![0; length]).unwrap();
+
+                let body = if head.starts_with("GET /federation/credentials") {
+                    r#"{"roleCredentials":{"accessKeyId":"ASIA","secretAccessKey":"secret","sessionToken":"token","expiration":32503680000000}}"#
+                } else {
+                    "<GetCallerIdentityResponse><GetCallerIdentityResult><Arn>arn:aws:sts::111111111111:assumed-role/AWSReservedSSO_Admin_abc/alice</Arn><UserId>AROA:alice</UserId><Account>111111111111</Account></GetCallerIdentityResult></GetCallerIdentityResponse>"
+                };
+                let mut stream = stream;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "each", "dev", "--", "true"])
+            .env("HOME", temp_dir.path())
+            .env("KEE_AWS_BIN", &aws)
+            .env("AWS_ENDPOINT_URL_SSO", &endpoint)
+            .env("AWS_ENDPOINT_URL_STS", &endpoint)
+            .output()
+            .expect("Failed to execute each command");
+
+        assert!(output.status.success());
+        assert!(!called.exists());
+    }
+
+    #[test]
+    fn test_rename_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "rename", "dev", "staging"])
+            .env("HOME", temp_dir.path())
+            .env_remove("KEE_CURRENT_PROFILE")
+            .output()
+            .expect("Failed to execute rename command");
+
+        assert!(output.status.success());
+        let config: KeeConfig = serde_json::from_str(
+            &fs::read_to_string(temp_dir.path().join(".kee").join("config.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(!config.profiles.contains_key("dev"));
+        assert_eq!(config.profiles["staging"].profile_name, "staging");
+        assert_eq!(config.current_profile.as_deref(), Some("staging"));
+
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("[profile staging]"));
+        assert!(!aws_config.contains("[profile dev]"));
+    }
+
+    #[test]
+    fn test_edit_profile_with_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args([
+                "run", "--", "edit", "dev", "--role", "ReadOnly", "--tag", "env=dev",
+            ])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute edit command");
+
+        assert!(output.status.success());
+        let config: KeeConfig = serde_json::from_str(
+            &fs::read_to_string(temp_dir.path().join(".kee").join("config.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.profiles["dev"].sso_role_name, "ReadOnly");
+        assert!(config.profiles["dev"].has_tag("env", "dev"));
+
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("sso_role_name = ReadOnly"));
+    }
+
+    #[test]
+    fn test_edit_profile_environment_shown_in_ls() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "edit",
+                "dev",
+                "--region",
+                "eu-west-1",
+                "--output",
+                "table",
+                "--env",
+                "TF_VAR_env=dev",
+            ])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute edit command");
+        assert!(output.status.success());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "ls"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute ls command");
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("eu-west-1"));
+        assert!(stdout.contains("table"));
+        assert!(stdout.contains("TF_VAR_env=dev"));
+    }
+
+    #[test]
+    fn test_role_chain_shown_in_ls() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+        let edit = |args: &[&str]| {
+            Command::new("cargo")
+                .args(["run", "--", "edit", "dev"])
+                .args(args)
+                .env("HOME", temp_dir.path())
+                .output()
+                .expect("Failed to execute edit command")
+        };
+
+        // Role options need a role to go with
+        let output = edit(&["--external-id", "acme-ext"]);
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("--assume-role"));
+
+        let output = edit(&[
+            "--assume-role",
+            "arn:aws:iam::222222222222:role/Deploy",
+            "--external-id",
+            "acme-ext",
+            "--role-duration",
+            "30m",
+        ]);
+        assert!(output.status.success());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "ls"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute ls command");
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Role chain:"));
+        assert!(stdout.contains("   Admin (111111111111)\n"));
+        assert!(stdout.contains("   → Deploy (222222222222), external ID acme-ext, 30m\n"));
+    }
+
+    #[test]
+    fn test_edit_profile_rejects_invalid_account() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "edit", "dev", "--account-id", "123"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute edit command");
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Invalid account ID"));
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(aws_config.contains("sso_account_id = 111111111111"));
+    }
+
+    #[test]
+    fn test_remove_profiles_with_pattern_and_yes() {
+        let temp_dir = TempDir::new().unwrap();
+        write_profile_fixture(temp_dir.path());
+
+        let output = Command::new("cargo")
+            .args(["run", "--", "rm", "d*", "--yes"])
+            .env("HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute remove command");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("has been removed"));
+        assert!(stdout.contains("no longer used by any profile"));
+
+        let config: KeeConfig = serde_json::from_str(
+            &fs::read_to_string(temp_dir.path().join(".kee").join("config.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(config.profiles.is_empty());
+        assert!(config.current_profile.is_none());
+
+        let aws_config = fs::read_to_string(temp_dir.path().join(".aws").join("config")).unwrap();
+        assert!(!aws_config.contains("[profile dev]"));
+        assert!(!aws_config.contains("[sso-session acme]"));
     }
 
     #[test]
@@ -128,8 +989,8 @@ mod integration_tests {
 
         // Command should succeed but show profile not found
         assert!(output.status.success());
-        let stdout = String::from_utf8(output.stdout).unwrap();
-        assert!(stdout.contains("not found"));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("not found"));
     }
 }
 
@@ -251,6 +1112,7 @@ mod config_file_tests {
             sso_account_id: "123456789012".to_string(),
             sso_role_name: "TestRole".to_string(),
             session_name: "test-session".to_string(),
+            ..Default::default()
         };
 
         original_config
@@ -286,6 +1148,7 @@ mod config_file_tests {
             sso_account_id: "111111111111".to_string(),
             sso_role_name: "ProdRole".to_string(),
             session_name: "prod-session".to_string(),
+            ..Default::default()
         };
 
         let profile2 = ProfileInfo {
@@ -295,6 +1158,7 @@ mod config_file_tests {
             sso_account_id: "222222222222".to_string(),
             sso_role_name: "DevRole".to_string(),
             session_name: "dev-session".to_string(),
+            ..Default::default()
         };
 
         // Add profiles